@@ -0,0 +1,26 @@
+--
+-- Adds the bookmarks table, run once on databases older than it:
+--
+--   psql -d xvdb -f database_schema/migrations/0001_bookmarks.sql
+--
+
+BEGIN;
+
+CREATE TABLE public.bookmarks (
+    user_id integer NOT NULL,
+    post_id integer NOT NULL,
+    unix_time bigint NOT NULL
+);
+
+ALTER TABLE public.bookmarks OWNER TO postgres;
+
+ALTER TABLE ONLY public.bookmarks
+    ADD CONSTRAINT bookmarks_pkey PRIMARY KEY (user_id, post_id);
+
+ALTER TABLE ONLY public.bookmarks
+    ADD CONSTRAINT fk_bookmark_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.bookmarks
+    ADD CONSTRAINT fk_bookmark_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+COMMIT;
@@ -0,0 +1,28 @@
+--
+-- Adds the post_revisions table and posts.edited_at, run once on databases
+-- older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0002_post_revisions.sql
+--
+
+BEGIN;
+
+CREATE TABLE public.post_revisions (
+    revision_id serial NOT NULL,
+    post_id integer NOT NULL,
+    text character varying(255),
+    image bytea,
+    unix_time bigint NOT NULL
+);
+
+ALTER TABLE public.post_revisions OWNER TO postgres;
+
+ALTER TABLE ONLY public.post_revisions
+    ADD CONSTRAINT post_revisions_pkey PRIMARY KEY (revision_id);
+
+ALTER TABLE ONLY public.post_revisions
+    ADD CONSTRAINT fk_revision_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+ALTER TABLE public.posts ADD COLUMN edited_at bigint;
+
+COMMIT;
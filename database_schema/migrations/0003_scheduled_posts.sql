@@ -0,0 +1,26 @@
+--
+-- Adds the scheduled_posts table, run once on databases older than it:
+--
+--   psql -d xvdb -f database_schema/migrations/0003_scheduled_posts.sql
+--
+
+BEGIN;
+
+CREATE TABLE public.scheduled_posts (
+    scheduled_id serial NOT NULL,
+    owner_id integer NOT NULL,
+    post_data text NOT NULL,
+    publish_at bigint NOT NULL
+);
+
+ALTER TABLE public.scheduled_posts OWNER TO postgres;
+
+ALTER TABLE ONLY public.scheduled_posts
+    ADD CONSTRAINT scheduled_posts_pkey PRIMARY KEY (scheduled_id);
+
+CREATE INDEX scheduled_posts_publish_at_idx ON public.scheduled_posts USING btree (publish_at);
+
+ALTER TABLE ONLY public.scheduled_posts
+    ADD CONSTRAINT fk_scheduled_owner_id FOREIGN KEY (owner_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+COMMIT;
@@ -0,0 +1,24 @@
+--
+-- Adds the drafts table, run once on databases older than it:
+--
+--   psql -d xvdb -f database_schema/migrations/0004_drafts.sql
+--
+
+BEGIN;
+
+CREATE TABLE public.drafts (
+    draft_id serial NOT NULL,
+    owner_id integer NOT NULL,
+    post_data text NOT NULL,
+    unix_time bigint NOT NULL
+);
+
+ALTER TABLE public.drafts OWNER TO postgres;
+
+ALTER TABLE ONLY public.drafts
+    ADD CONSTRAINT drafts_pkey PRIMARY KEY (draft_id);
+
+ALTER TABLE ONLY public.drafts
+    ADD CONSTRAINT fk_draft_owner_id FOREIGN KEY (owner_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+COMMIT;
@@ -0,0 +1,55 @@
+--
+-- Adds the polls, poll_options and poll_votes tables, run once on databases
+-- older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0005_polls.sql
+--
+
+BEGIN;
+
+CREATE TABLE public.polls (
+    post_id integer NOT NULL,
+    closes_at bigint NOT NULL
+);
+
+ALTER TABLE public.polls OWNER TO postgres;
+
+ALTER TABLE ONLY public.polls
+    ADD CONSTRAINT polls_pkey PRIMARY KEY (post_id);
+
+ALTER TABLE ONLY public.polls
+    ADD CONSTRAINT fk_poll_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+CREATE TABLE public.poll_options (
+    post_id integer NOT NULL,
+    option_index integer NOT NULL,
+    text character varying(255) NOT NULL
+);
+
+ALTER TABLE public.poll_options OWNER TO postgres;
+
+ALTER TABLE ONLY public.poll_options
+    ADD CONSTRAINT poll_options_pkey PRIMARY KEY (post_id, option_index);
+
+ALTER TABLE ONLY public.poll_options
+    ADD CONSTRAINT fk_poll_option_post_id FOREIGN KEY (post_id) REFERENCES public.polls(post_id) ON DELETE CASCADE;
+
+CREATE TABLE public.poll_votes (
+    post_id integer NOT NULL,
+    user_id integer NOT NULL,
+    option_index integer NOT NULL,
+    unix_time bigint NOT NULL
+);
+
+ALTER TABLE public.poll_votes OWNER TO postgres;
+
+ALTER TABLE ONLY public.poll_votes
+    ADD CONSTRAINT poll_votes_pkey PRIMARY KEY (post_id, user_id);
+
+ALTER TABLE ONLY public.poll_votes
+    ADD CONSTRAINT fk_poll_vote_option FOREIGN KEY (post_id, option_index) REFERENCES public.poll_options(post_id, option_index) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.poll_votes
+    ADD CONSTRAINT fk_poll_vote_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+COMMIT;
@@ -0,0 +1,14 @@
+--
+-- Adds users.pinned_post_id, run once on databases older than it:
+--
+--   psql -d xvdb -f database_schema/migrations/0006_pinned_posts.sql
+--
+
+BEGIN;
+
+ALTER TABLE public.users ADD COLUMN pinned_post_id integer;
+
+ALTER TABLE ONLY public.users
+    ADD CONSTRAINT fk_pinned_post_id FOREIGN KEY (pinned_post_id) REFERENCES public.posts(post_id) ON DELETE SET NULL;
+
+COMMIT;
@@ -0,0 +1,38 @@
+--
+-- Adds posts.visibility and the post_mentions table, run once on databases
+-- older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0007_post_visibility.sql
+--
+
+BEGIN;
+
+ALTER TABLE public.posts
+    ADD COLUMN visibility character varying(16) DEFAULT 'public'::character varying NOT NULL,
+    ADD CONSTRAINT posts_visibility_check CHECK (((visibility)::text = ANY ((ARRAY['public'::character varying, 'followers'::character varying, 'mentioned'::character varying])::text[])));
+
+CREATE TABLE public.post_mentions (
+    post_id integer NOT NULL,
+    user_id integer NOT NULL
+);
+
+ALTER TABLE public.post_mentions OWNER TO postgres;
+
+ALTER TABLE ONLY public.post_mentions
+    ADD CONSTRAINT post_mentions_pkey PRIMARY KEY (post_id, user_id);
+
+ALTER TABLE ONLY public.post_mentions
+    ADD CONSTRAINT fk_mention_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.post_mentions
+    ADD CONSTRAINT fk_mention_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+-- The same @handles parse_mentions finds in the text of existing posts.
+INSERT INTO public.post_mentions (post_id, user_id)
+SELECT p.post_id, u.id
+FROM public.posts p
+CROSS JOIN LATERAL regexp_matches(COALESCE(p.text, ''), '@(\w+)', 'g') AS m
+JOIN public.users u ON u.userat = lower(m[1])
+ON CONFLICT DO NOTHING;
+
+COMMIT;
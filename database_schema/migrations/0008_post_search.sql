@@ -0,0 +1,15 @@
+--
+-- Adds the posts.search_vector column and its index, run once on databases
+-- older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0008_post_search.sql
+--
+
+BEGIN;
+
+ALTER TABLE public.posts
+    ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (to_tsvector('simple'::regconfig, (COALESCE(text, ''::character varying))::text)) STORED;
+
+CREATE INDEX posts_search_vector_idx ON public.posts USING gin (search_vector);
+
+COMMIT;
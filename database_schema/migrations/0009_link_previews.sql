@@ -0,0 +1,27 @@
+--
+-- Adds the link_previews table, run once on databases older than it:
+--
+--   psql -d xvdb -f database_schema/migrations/0009_link_previews.sql
+--
+
+BEGIN;
+
+CREATE TABLE public.link_previews (
+    url text NOT NULL,
+    status character varying(16) DEFAULT 'pending'::character varying NOT NULL,
+    title text,
+    description text,
+    image text,
+    site_name text,
+    updated_at bigint NOT NULL,
+    CONSTRAINT link_previews_status_check CHECK (((status)::text = ANY ((ARRAY['pending'::character varying, 'fetching'::character varying, 'ready'::character varying, 'failed'::character varying])::text[])))
+);
+
+ALTER TABLE public.link_previews OWNER TO postgres;
+
+ALTER TABLE ONLY public.link_previews
+    ADD CONSTRAINT link_previews_pkey PRIMARY KEY (url);
+
+CREATE INDEX link_previews_status_idx ON public.link_previews USING btree (status);
+
+COMMIT;
@@ -0,0 +1,18 @@
+--
+-- Adds posts.content_warning, posts.sensitive and users.sensitive_media, run
+-- once on databases older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0010_content_warnings.sql
+--
+
+BEGIN;
+
+ALTER TABLE public.posts
+    ADD COLUMN content_warning character varying(100),
+    ADD COLUMN sensitive boolean DEFAULT false NOT NULL;
+
+ALTER TABLE public.users
+    ADD COLUMN sensitive_media character varying(8) DEFAULT 'blur'::character varying NOT NULL,
+    ADD CONSTRAINT users_sensitive_media_check CHECK (((sensitive_media)::text = ANY ((ARRAY['blur'::character varying, 'hide'::character varying])::text[])));
+
+COMMIT;
@@ -0,0 +1,49 @@
+--
+-- Adds the post_attachments and comment_attachments tables and the primary
+-- key of comments they reference, run once on databases older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0011_attachments.sql
+--
+
+BEGIN;
+
+ALTER TABLE ONLY public.comments
+    ADD CONSTRAINT comments_pkey PRIMARY KEY (post_id);
+
+CREATE TABLE public.post_attachments (
+    post_id integer NOT NULL,
+    "position" integer NOT NULL,
+    data bytea NOT NULL,
+    alt_text character varying(1000),
+    width integer NOT NULL,
+    height integer NOT NULL,
+    mime_type character varying(64) NOT NULL
+);
+
+ALTER TABLE public.post_attachments OWNER TO postgres;
+
+ALTER TABLE ONLY public.post_attachments
+    ADD CONSTRAINT post_attachments_pkey PRIMARY KEY (post_id, "position");
+
+ALTER TABLE ONLY public.post_attachments
+    ADD CONSTRAINT fk_attachment_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+CREATE TABLE public.comment_attachments (
+    comment_id integer NOT NULL,
+    "position" integer NOT NULL,
+    data bytea NOT NULL,
+    alt_text character varying(1000),
+    width integer NOT NULL,
+    height integer NOT NULL,
+    mime_type character varying(64) NOT NULL
+);
+
+ALTER TABLE public.comment_attachments OWNER TO postgres;
+
+ALTER TABLE ONLY public.comment_attachments
+    ADD CONSTRAINT comment_attachments_pkey PRIMARY KEY (comment_id, "position");
+
+ALTER TABLE ONLY public.comment_attachments
+    ADD CONSTRAINT fk_attachment_comment_id FOREIGN KEY (comment_id) REFERENCES public.comments(post_id) ON DELETE CASCADE;
+
+COMMIT;
@@ -0,0 +1,64 @@
+--
+-- Adds the media table and the columns referencing it, run once on databases
+-- older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0012_media.sql
+--
+-- Images used to be kept in the rows themselves, as the data URL the client
+-- sent. Those columns are kept here, the server moves their content to the
+-- media store when it starts and drops each column once all its rows are
+-- moved, see src/media/legacy.rs. Rows whose content can't be stored are
+-- logged and keep the column around, set it to NULL on them once they are
+-- dealt with. Run the later migrations before starting the server.
+--
+
+BEGIN;
+
+CREATE TABLE public.media (
+    media_id character varying(64) NOT NULL,
+    mime_type character varying(64) NOT NULL,
+    size bigint NOT NULL,
+    unix_time bigint NOT NULL
+);
+
+ALTER TABLE public.media OWNER TO postgres;
+
+ALTER TABLE ONLY public.media
+    ADD CONSTRAINT media_pkey PRIMARY KEY (media_id);
+
+ALTER TABLE public.posts ADD COLUMN image_id character varying(64);
+
+ALTER TABLE ONLY public.posts
+    ADD CONSTRAINT fk_image_id FOREIGN KEY (image_id) REFERENCES public.media(media_id);
+
+ALTER TABLE public.comments ADD COLUMN image_id character varying(64);
+
+ALTER TABLE ONLY public.comments
+    ADD CONSTRAINT fk_comment_image_id FOREIGN KEY (image_id) REFERENCES public.media(media_id);
+
+ALTER TABLE public.post_revisions ADD COLUMN image_id character varying(64);
+
+ALTER TABLE ONLY public.post_revisions
+    ADD CONSTRAINT fk_revision_image_id FOREIGN KEY (image_id) REFERENCES public.media(media_id);
+
+ALTER TABLE public.users ADD COLUMN icon_id character varying(64);
+
+ALTER TABLE ONLY public.users
+    ADD CONSTRAINT fk_icon_id FOREIGN KEY (icon_id) REFERENCES public.media(media_id);
+
+-- set NOT NULL, and data dropped, once the data is moved
+ALTER TABLE public.post_attachments
+    ADD COLUMN media_id character varying(64),
+    ALTER COLUMN data DROP NOT NULL;
+
+ALTER TABLE ONLY public.post_attachments
+    ADD CONSTRAINT fk_attachment_media_id FOREIGN KEY (media_id) REFERENCES public.media(media_id);
+
+ALTER TABLE public.comment_attachments
+    ADD COLUMN media_id character varying(64),
+    ALTER COLUMN data DROP NOT NULL;
+
+ALTER TABLE ONLY public.comment_attachments
+    ADD CONSTRAINT fk_attachment_media_id FOREIGN KEY (media_id) REFERENCES public.media(media_id);
+
+COMMIT;
@@ -0,0 +1,32 @@
+--
+-- Adds the size and blurhash of media and the media_variants table, run once
+-- on databases older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0013_media_variants.sql
+--
+
+BEGIN;
+
+ALTER TABLE public.media
+    ADD COLUMN width integer,
+    ADD COLUMN height integer,
+    ADD COLUMN blurhash character varying(64);
+
+CREATE TABLE public.media_variants (
+    media_id character varying(64) NOT NULL,
+    name character varying(16) NOT NULL,
+    variant_id character varying(64) NOT NULL
+);
+
+ALTER TABLE public.media_variants OWNER TO postgres;
+
+ALTER TABLE ONLY public.media_variants
+    ADD CONSTRAINT media_variants_pkey PRIMARY KEY (media_id, name);
+
+ALTER TABLE ONLY public.media_variants
+    ADD CONSTRAINT fk_variant_media_id FOREIGN KEY (media_id) REFERENCES public.media(media_id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.media_variants
+    ADD CONSTRAINT fk_variant_variant_id FOREIGN KEY (variant_id) REFERENCES public.media(media_id);
+
+COMMIT;
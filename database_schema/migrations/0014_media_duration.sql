@@ -0,0 +1,11 @@
+--
+-- Adds media.duration_ms, run once on databases older than it:
+--
+--   psql -d xvdb -f database_schema/migrations/0014_media_duration.sql
+--
+
+BEGIN;
+
+ALTER TABLE public.media ADD COLUMN duration_ms integer;
+
+COMMIT;
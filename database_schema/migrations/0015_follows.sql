@@ -0,0 +1,49 @@
+--
+-- Moves the follow graph from the followers and following arrays of users to
+-- the follows table. Databases created from xvdb_schema.sql already have it,
+-- older ones run this once:
+--
+--   psql -d xvdb -f database_schema/migrations/0015_follows.sql
+--
+
+BEGIN;
+
+CREATE TABLE public.follows (
+    follower_id integer NOT NULL,
+    followee_id integer NOT NULL,
+    created_at bigint NOT NULL,
+    CONSTRAINT follows_not_self CHECK ((follower_id <> followee_id))
+);
+
+ALTER TABLE public.follows OWNER TO postgres;
+
+ALTER TABLE ONLY public.follows
+    ADD CONSTRAINT follows_pkey PRIMARY KEY (follower_id, followee_id);
+
+CREATE INDEX follows_followee_id_idx ON public.follows USING btree (followee_id, created_at);
+
+ALTER TABLE ONLY public.follows
+    ADD CONSTRAINT fk_followee_id FOREIGN KEY (followee_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.follows
+    ADD CONSTRAINT fk_follower_id FOREIGN KEY (follower_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+-- Only follows recorded on both sides are kept, which is what is_following
+-- used to check, a failed update could leave one side behind. When they
+-- were made isn't known, the order of the followers array is kept instead.
+INSERT INTO public.follows (follower_id, followee_id, created_at)
+SELECT f.follower_id, u.id AS followee_id, (extract(epoch FROM now()) * 1000)::bigint + f.n
+FROM public.users u
+CROSS JOIN LATERAL unnest(u.followers) WITH ORDINALITY AS f(follower_id, n)
+JOIN public.users follower ON follower.id = f.follower_id
+WHERE f.follower_id <> u.id
+    AND u.id = ANY(follower.following)
+ON CONFLICT DO NOTHING;
+
+UPDATE public.users SET
+    followerscount = (SELECT count(*) FROM public.follows WHERE followee_id = users.id),
+    followingcount = (SELECT count(*) FROM public.follows WHERE follower_id = users.id);
+
+ALTER TABLE public.users DROP COLUMN followers, DROP COLUMN following;
+
+COMMIT;
@@ -0,0 +1,29 @@
+--
+-- Adds the blocks table, run once on databases older than it:
+--
+--   psql -d xvdb -f database_schema/migrations/0016_blocks.sql
+--
+
+BEGIN;
+
+CREATE TABLE public.blocks (
+    blocker_id integer NOT NULL,
+    blocked_id integer NOT NULL,
+    created_at bigint NOT NULL,
+    CONSTRAINT blocks_not_self CHECK ((blocker_id <> blocked_id))
+);
+
+ALTER TABLE public.blocks OWNER TO postgres;
+
+ALTER TABLE ONLY public.blocks
+    ADD CONSTRAINT blocks_pkey PRIMARY KEY (blocker_id, blocked_id);
+
+CREATE INDEX blocks_blocked_id_idx ON public.blocks USING btree (blocked_id);
+
+ALTER TABLE ONLY public.blocks
+    ADD CONSTRAINT fk_blocked_id FOREIGN KEY (blocked_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.blocks
+    ADD CONSTRAINT fk_blocker_id FOREIGN KEY (blocker_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+COMMIT;
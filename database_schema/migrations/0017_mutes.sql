@@ -0,0 +1,61 @@
+--
+-- Adds the mutes and muted_words tables, run once on databases older than
+-- them:
+--
+--   psql -d xvdb -f database_schema/migrations/0017_mutes.sql
+--
+
+BEGIN;
+
+CREATE TABLE public.mutes (
+    muter_id integer NOT NULL,
+    muted_id integer NOT NULL,
+    created_at bigint NOT NULL,
+    CONSTRAINT mutes_not_self CHECK ((muter_id <> muted_id))
+);
+
+ALTER TABLE public.mutes OWNER TO postgres;
+
+ALTER TABLE ONLY public.mutes
+    ADD CONSTRAINT mutes_pkey PRIMARY KEY (muter_id, muted_id);
+
+ALTER TABLE ONLY public.mutes
+    ADD CONSTRAINT fk_muted_id FOREIGN KEY (muted_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.mutes
+    ADD CONSTRAINT fk_muter_id FOREIGN KEY (muter_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+CREATE TABLE public.muted_words (
+    word_id integer NOT NULL,
+    user_id integer NOT NULL,
+    phrase character varying(100) NOT NULL,
+    expires_at bigint,
+    created_at bigint NOT NULL
+);
+
+ALTER TABLE public.muted_words OWNER TO postgres;
+
+CREATE SEQUENCE public.muted_words_word_id_seq
+    AS integer
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+ALTER SEQUENCE public.muted_words_word_id_seq OWNER TO postgres;
+
+ALTER SEQUENCE public.muted_words_word_id_seq OWNED BY public.muted_words.word_id;
+
+ALTER TABLE ONLY public.muted_words ALTER COLUMN word_id SET DEFAULT nextval('public.muted_words_word_id_seq'::regclass);
+
+ALTER TABLE ONLY public.muted_words
+    ADD CONSTRAINT muted_words_pkey PRIMARY KEY (word_id);
+
+ALTER TABLE ONLY public.muted_words
+    ADD CONSTRAINT muted_words_user_id_phrase_key UNIQUE (user_id, phrase);
+
+ALTER TABLE ONLY public.muted_words
+    ADD CONSTRAINT fk_muted_word_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+COMMIT;
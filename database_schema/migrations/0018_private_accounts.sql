@@ -0,0 +1,32 @@
+--
+-- Adds private accounts and the follow_requests table, run once on databases
+-- older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0018_private_accounts.sql
+--
+
+BEGIN;
+
+ALTER TABLE public.users ADD COLUMN private boolean DEFAULT false NOT NULL;
+
+CREATE TABLE public.follow_requests (
+    requester_id integer NOT NULL,
+    target_id integer NOT NULL,
+    created_at bigint NOT NULL,
+    CONSTRAINT follow_requests_not_self CHECK ((requester_id <> target_id))
+);
+
+ALTER TABLE public.follow_requests OWNER TO postgres;
+
+ALTER TABLE ONLY public.follow_requests
+    ADD CONSTRAINT follow_requests_pkey PRIMARY KEY (requester_id, target_id);
+
+CREATE INDEX follow_requests_target_id_idx ON public.follow_requests USING btree (target_id, created_at);
+
+ALTER TABLE ONLY public.follow_requests
+    ADD CONSTRAINT fk_follow_request_requester_id FOREIGN KEY (requester_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.follow_requests
+    ADD CONSTRAINT fk_follow_request_target_id FOREIGN KEY (target_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+COMMIT;
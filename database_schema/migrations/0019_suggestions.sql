@@ -0,0 +1,31 @@
+--
+-- Adds the dismissed_suggestions table and the posts indexes the suggestions
+-- rely on, run once on databases older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0019_suggestions.sql
+--
+
+BEGIN;
+
+CREATE TABLE public.dismissed_suggestions (
+    user_id integer NOT NULL,
+    dismissed_id integer NOT NULL,
+    created_at bigint NOT NULL
+);
+
+ALTER TABLE public.dismissed_suggestions OWNER TO postgres;
+
+ALTER TABLE ONLY public.dismissed_suggestions
+    ADD CONSTRAINT dismissed_suggestions_pkey PRIMARY KEY (user_id, dismissed_id);
+
+ALTER TABLE ONLY public.dismissed_suggestions
+    ADD CONSTRAINT fk_dismissed_id FOREIGN KEY (dismissed_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.dismissed_suggestions
+    ADD CONSTRAINT fk_dismissed_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+CREATE INDEX posts_owner_id_unix_time_idx ON public.posts USING btree (owner_id, unix_time);
+
+CREATE INDEX posts_unix_time_idx ON public.posts USING btree (unix_time);
+
+COMMIT;
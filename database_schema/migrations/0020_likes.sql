@@ -0,0 +1,75 @@
+--
+-- Moves likes from the likes arrays of posts and comments to the post_likes
+-- and comment_likes tables, run once on databases older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0020_likes.sql
+--
+
+BEGIN;
+
+CREATE TABLE public.post_likes (
+    post_id integer NOT NULL,
+    user_id integer NOT NULL,
+    created_at bigint NOT NULL
+);
+
+ALTER TABLE public.post_likes OWNER TO postgres;
+
+ALTER TABLE ONLY public.post_likes
+    ADD CONSTRAINT post_likes_pkey PRIMARY KEY (post_id, user_id);
+
+CREATE INDEX post_likes_post_id_created_at_idx ON public.post_likes USING btree (post_id, created_at);
+
+ALTER TABLE ONLY public.post_likes
+    ADD CONSTRAINT fk_post_like_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.post_likes
+    ADD CONSTRAINT fk_post_like_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+CREATE TABLE public.comment_likes (
+    comment_id integer NOT NULL,
+    user_id integer NOT NULL,
+    created_at bigint NOT NULL
+);
+
+ALTER TABLE public.comment_likes OWNER TO postgres;
+
+ALTER TABLE ONLY public.comment_likes
+    ADD CONSTRAINT comment_likes_pkey PRIMARY KEY (comment_id, user_id);
+
+CREATE INDEX comment_likes_comment_id_created_at_idx ON public.comment_likes USING btree (comment_id, created_at);
+
+ALTER TABLE ONLY public.comment_likes
+    ADD CONSTRAINT fk_comment_like_comment_id FOREIGN KEY (comment_id) REFERENCES public.comments(post_id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.comment_likes
+    ADD CONSTRAINT fk_comment_like_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+-- When the likes were made isn't known, the order of the arrays is kept
+-- instead. Duplicates left by double clicks and likes of deleted users are
+-- dropped.
+INSERT INTO public.post_likes (post_id, user_id, created_at)
+SELECT p.post_id, l.user_id, p.unix_time + l.n
+FROM public.posts p
+CROSS JOIN LATERAL unnest(p.likes) WITH ORDINALITY AS l(user_id, n)
+JOIN public.users u ON u.id = l.user_id
+ON CONFLICT DO NOTHING;
+
+INSERT INTO public.comment_likes (comment_id, user_id, created_at)
+SELECT c.post_id, l.user_id, c.unix_time + l.n
+FROM public.comments c
+CROSS JOIN LATERAL unnest(c.likes) WITH ORDINALITY AS l(user_id, n)
+JOIN public.users u ON u.id = l.user_id
+ON CONFLICT DO NOTHING;
+
+UPDATE public.posts SET
+    likescount = (SELECT count(*) FROM public.post_likes WHERE post_id = posts.post_id);
+
+UPDATE public.comments SET
+    likescount = (SELECT count(*) FROM public.comment_likes WHERE comment_id = comments.post_id);
+
+ALTER TABLE public.posts DROP COLUMN likes;
+
+ALTER TABLE public.comments DROP COLUMN likes;
+
+COMMIT;
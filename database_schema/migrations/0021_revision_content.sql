@@ -0,0 +1,34 @@
+--
+-- Keeps the content warning, sensitive flag and attachments of posts in their
+-- revisions, run once on databases older than them:
+--
+--   psql -d xvdb -f database_schema/migrations/0021_revision_content.sql
+--
+-- Revisions saved before it keep no attachments and aren't sensitive.
+--
+
+BEGIN;
+
+ALTER TABLE public.post_revisions
+    ADD COLUMN content_warning character varying(100),
+    ADD COLUMN sensitive boolean DEFAULT false NOT NULL;
+
+CREATE TABLE public.post_revision_attachments (
+    revision_id integer NOT NULL,
+    "position" integer NOT NULL,
+    media_id character varying(64) NOT NULL,
+    alt_text character varying(1000)
+);
+
+ALTER TABLE public.post_revision_attachments OWNER TO postgres;
+
+ALTER TABLE ONLY public.post_revision_attachments
+    ADD CONSTRAINT post_revision_attachments_pkey PRIMARY KEY (revision_id, "position");
+
+ALTER TABLE ONLY public.post_revision_attachments
+    ADD CONSTRAINT fk_revision_attachment_revision_id FOREIGN KEY (revision_id) REFERENCES public.post_revisions(revision_id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.post_revision_attachments
+    ADD CONSTRAINT fk_revision_attachment_media_id FOREIGN KEY (media_id) REFERENCES public.media(media_id);
+
+COMMIT;
@@ -0,0 +1,11 @@
+--
+-- Adds scheduled_posts.failed, run once on databases older than it:
+--
+--   psql -d xvdb -f database_schema/migrations/0022_scheduled_posts_failed.sql
+--
+
+BEGIN;
+
+ALTER TABLE public.scheduled_posts ADD COLUMN failed boolean DEFAULT false NOT NULL;
+
+COMMIT;
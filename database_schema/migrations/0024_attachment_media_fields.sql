@@ -0,0 +1,29 @@
+--
+-- Drops the size and type kept on attachments, they're read from the media
+-- now. Media stored without a size gets the one of its attachments first.
+-- Run once on databases older than it:
+--
+--   psql -d xvdb -f database_schema/migrations/0024_attachment_media_fields.sql
+--
+
+BEGIN;
+
+UPDATE public.media m SET width = a.width, height = a.height
+    FROM public.post_attachments a
+    WHERE a.media_id = m.media_id AND m.width IS NULL;
+
+UPDATE public.media m SET width = a.width, height = a.height
+    FROM public.comment_attachments a
+    WHERE a.media_id = m.media_id AND m.width IS NULL;
+
+ALTER TABLE public.post_attachments
+    DROP COLUMN width,
+    DROP COLUMN height,
+    DROP COLUMN mime_type;
+
+ALTER TABLE public.comment_attachments
+    DROP COLUMN width,
+    DROP COLUMN height,
+    DROP COLUMN mime_type;
+
+COMMIT;
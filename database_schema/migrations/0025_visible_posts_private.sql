@@ -0,0 +1,23 @@
+--
+-- Moves the private account check into visible_posts, so posts of private
+-- accounts are only returned to their followers. Run once on databases older
+-- than it:
+--
+--   psql -d xvdb -f database_schema/migrations/0025_visible_posts_private.sql
+--
+
+BEGIN;
+
+CREATE OR REPLACE FUNCTION public.visible_posts(viewer_id integer) RETURNS SETOF public.posts
+    LANGUAGE sql STABLE
+    AS $$
+    SELECT p.* FROM public.posts p
+    WHERE (p.visibility = 'public' OR p.owner_id = viewer_id
+            OR (p.visibility = 'followers' AND EXISTS(SELECT 1 FROM public.follows f WHERE f.follower_id = viewer_id AND f.followee_id = p.owner_id))
+            OR (p.visibility = 'mentioned' AND EXISTS(SELECT 1 FROM public.post_mentions m WHERE m.post_id = p.post_id AND m.user_id = viewer_id)))
+        AND (p.owner_id = viewer_id OR NOT EXISTS(SELECT 1 FROM public.users o WHERE o.id = p.owner_id AND o.private)
+            OR EXISTS(SELECT 1 FROM public.follows f WHERE f.follower_id = viewer_id AND f.followee_id = p.owner_id))
+        AND NOT EXISTS(SELECT 1 FROM public.blocks bl WHERE (bl.blocker_id = p.owner_id AND bl.blocked_id = viewer_id) OR (bl.blocker_id = viewer_id AND bl.blocked_id = p.owner_id))
+$$;
+
+COMMIT;
@@ -20,6 +20,104 @@ SET default_tablespace = '';
 
 SET default_table_access_method = heap;
 
+--
+-- Name: posts; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.posts (
+    text character varying(255),
+    owner_id integer NOT NULL,
+    likescount integer NOT NULL,
+    image_id character varying(64),
+    post_id integer NOT NULL,
+    unix_time bigint NOT NULL,
+    commentscount integer DEFAULT 0 NOT NULL,
+    comments integer[],
+    edited boolean DEFAULT false NOT NULL,
+    edited_at bigint,
+    visibility character varying(16) DEFAULT 'public'::character varying NOT NULL,
+    search_vector tsvector GENERATED ALWAYS AS (to_tsvector('simple'::regconfig, (COALESCE(text, ''::character varying))::text)) STORED,
+    content_warning character varying(100),
+    sensitive boolean DEFAULT false NOT NULL,
+    CONSTRAINT posts_visibility_check CHECK (((visibility)::text = ANY ((ARRAY['public'::character varying, 'followers'::character varying, 'mentioned'::character varying])::text[])))
+);
+
+
+ALTER TABLE public.posts OWNER TO postgres;
+
+--
+-- Name: visible_posts(integer); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE FUNCTION public.visible_posts(viewer_id integer) RETURNS SETOF public.posts
+    LANGUAGE sql STABLE
+    AS $$
+    SELECT p.* FROM public.posts p
+    WHERE (p.visibility = 'public' OR p.owner_id = viewer_id
+            OR (p.visibility = 'followers' AND EXISTS(SELECT 1 FROM public.follows f WHERE f.follower_id = viewer_id AND f.followee_id = p.owner_id))
+            OR (p.visibility = 'mentioned' AND EXISTS(SELECT 1 FROM public.post_mentions m WHERE m.post_id = p.post_id AND m.user_id = viewer_id)))
+        AND (p.owner_id = viewer_id OR NOT EXISTS(SELECT 1 FROM public.users o WHERE o.id = p.owner_id AND o.private)
+            OR EXISTS(SELECT 1 FROM public.follows f WHERE f.follower_id = viewer_id AND f.followee_id = p.owner_id))
+        AND NOT EXISTS(SELECT 1 FROM public.blocks bl WHERE (bl.blocker_id = p.owner_id AND bl.blocked_id = viewer_id) OR (bl.blocker_id = viewer_id AND bl.blocked_id = p.owner_id))
+$$;
+
+
+ALTER FUNCTION public.visible_posts(viewer_id integer) OWNER TO postgres;
+
+--
+-- Name: blocks; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.blocks (
+    blocker_id integer NOT NULL,
+    blocked_id integer NOT NULL,
+    created_at bigint NOT NULL,
+    CONSTRAINT blocks_not_self CHECK ((blocker_id <> blocked_id))
+);
+
+
+ALTER TABLE public.blocks OWNER TO postgres;
+
+--
+-- Name: bookmarks; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.bookmarks (
+    user_id integer NOT NULL,
+    post_id integer NOT NULL,
+    unix_time bigint NOT NULL
+);
+
+
+ALTER TABLE public.bookmarks OWNER TO postgres;
+
+--
+-- Name: comment_attachments; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.comment_attachments (
+    comment_id integer NOT NULL,
+    "position" integer NOT NULL,
+    media_id character varying(64) NOT NULL,
+    alt_text character varying(1000)
+);
+
+
+ALTER TABLE public.comment_attachments OWNER TO postgres;
+
+--
+-- Name: comment_likes; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.comment_likes (
+    comment_id integer NOT NULL,
+    user_id integer NOT NULL,
+    created_at bigint NOT NULL
+);
+
+
+ALTER TABLE public.comment_likes OWNER TO postgres;
+
 --
 -- Name: comments; Type: TABLE; Schema: public; Owner: postgres
 --
@@ -28,8 +126,7 @@ CREATE TABLE public.comments (
     text character varying(255),
     owner_id integer NOT NULL,
     likescount integer NOT NULL,
-    image bytea,
-    likes integer[],
+    image_id character varying(64),
     unix_time bigint NOT NULL,
     commentscount integer DEFAULT 0 NOT NULL,
     comments integer[],
@@ -63,24 +160,312 @@ ALTER SEQUENCE public.comments_post_id_seq OWNED BY public.comments.post_id;
 
 
 --
--- Name: posts; Type: TABLE; Schema: public; Owner: postgres
+-- Name: dismissed_suggestions; Type: TABLE; Schema: public; Owner: postgres
 --
 
-CREATE TABLE public.posts (
-    text character varying(255),
+CREATE TABLE public.dismissed_suggestions (
+    user_id integer NOT NULL,
+    dismissed_id integer NOT NULL,
+    created_at bigint NOT NULL
+);
+
+
+ALTER TABLE public.dismissed_suggestions OWNER TO postgres;
+
+--
+-- Name: drafts; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.drafts (
+    draft_id integer NOT NULL,
     owner_id integer NOT NULL,
-    likescount integer NOT NULL,
-    image bytea,
-    likes integer[],
+    post_data text NOT NULL,
+    unix_time bigint NOT NULL
+);
+
+
+ALTER TABLE public.drafts OWNER TO postgres;
+
+--
+-- Name: drafts_draft_id_seq; Type: SEQUENCE; Schema: public; Owner: postgres
+--
+
+CREATE SEQUENCE public.drafts_draft_id_seq
+    AS integer
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+
+ALTER SEQUENCE public.drafts_draft_id_seq OWNER TO postgres;
+
+--
+-- Name: drafts_draft_id_seq; Type: SEQUENCE OWNED BY; Schema: public; Owner: postgres
+--
+
+ALTER SEQUENCE public.drafts_draft_id_seq OWNED BY public.drafts.draft_id;
+
+
+--
+-- Name: follow_requests; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.follow_requests (
+    requester_id integer NOT NULL,
+    target_id integer NOT NULL,
+    created_at bigint NOT NULL,
+    CONSTRAINT follow_requests_not_self CHECK ((requester_id <> target_id))
+);
+
+
+ALTER TABLE public.follow_requests OWNER TO postgres;
+
+--
+-- Name: follows; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.follows (
+    follower_id integer NOT NULL,
+    followee_id integer NOT NULL,
+    created_at bigint NOT NULL,
+    CONSTRAINT follows_not_self CHECK ((follower_id <> followee_id))
+);
+
+
+ALTER TABLE public.follows OWNER TO postgres;
+
+--
+-- Name: link_previews; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.link_previews (
+    url text NOT NULL,
+    status character varying(16) DEFAULT 'pending'::character varying NOT NULL,
+    title text,
+    description text,
+    image text,
+    site_name text,
+    updated_at bigint NOT NULL,
+    CONSTRAINT link_previews_status_check CHECK (((status)::text = ANY ((ARRAY['pending'::character varying, 'fetching'::character varying, 'ready'::character varying, 'failed'::character varying])::text[])))
+);
+
+
+ALTER TABLE public.link_previews OWNER TO postgres;
+
+--
+-- Name: media; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.media (
+    media_id character varying(64) NOT NULL,
+    mime_type character varying(64) NOT NULL,
+    size bigint NOT NULL,
+    width integer,
+    height integer,
+    blurhash character varying(64),
+    duration_ms integer,
+    unix_time bigint NOT NULL
+);
+
+
+ALTER TABLE public.media OWNER TO postgres;
+
+--
+-- Name: media_variants; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.media_variants (
+    media_id character varying(64) NOT NULL,
+    name character varying(16) NOT NULL,
+    variant_id character varying(64) NOT NULL
+);
+
+
+ALTER TABLE public.media_variants OWNER TO postgres;
+
+--
+-- Name: muted_words; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.muted_words (
+    word_id integer NOT NULL,
+    user_id integer NOT NULL,
+    phrase character varying(100) NOT NULL,
+    expires_at bigint,
+    created_at bigint NOT NULL
+);
+
+
+ALTER TABLE public.muted_words OWNER TO postgres;
+
+--
+-- Name: muted_words_word_id_seq; Type: SEQUENCE; Schema: public; Owner: postgres
+--
+
+CREATE SEQUENCE public.muted_words_word_id_seq
+    AS integer
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+
+ALTER SEQUENCE public.muted_words_word_id_seq OWNER TO postgres;
+
+--
+-- Name: muted_words_word_id_seq; Type: SEQUENCE OWNED BY; Schema: public; Owner: postgres
+--
+
+ALTER SEQUENCE public.muted_words_word_id_seq OWNED BY public.muted_words.word_id;
+
+
+--
+-- Name: mutes; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.mutes (
+    muter_id integer NOT NULL,
+    muted_id integer NOT NULL,
+    created_at bigint NOT NULL,
+    CONSTRAINT mutes_not_self CHECK ((muter_id <> muted_id))
+);
+
+
+ALTER TABLE public.mutes OWNER TO postgres;
+
+--
+-- Name: poll_options; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.poll_options (
+    post_id integer NOT NULL,
+    option_index integer NOT NULL,
+    text character varying(255) NOT NULL
+);
+
+
+ALTER TABLE public.poll_options OWNER TO postgres;
+
+--
+-- Name: poll_votes; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.poll_votes (
+    post_id integer NOT NULL,
+    user_id integer NOT NULL,
+    option_index integer NOT NULL,
+    unix_time bigint NOT NULL
+);
+
+
+ALTER TABLE public.poll_votes OWNER TO postgres;
+
+--
+-- Name: polls; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.polls (
+    post_id integer NOT NULL,
+    closes_at bigint NOT NULL
+);
+
+
+ALTER TABLE public.polls OWNER TO postgres;
+
+--
+-- Name: post_attachments; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.post_attachments (
+    post_id integer NOT NULL,
+    "position" integer NOT NULL,
+    media_id character varying(64) NOT NULL,
+    alt_text character varying(1000)
+);
+
+
+ALTER TABLE public.post_attachments OWNER TO postgres;
+
+--
+-- Name: post_likes; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.post_likes (
+    post_id integer NOT NULL,
+    user_id integer NOT NULL,
+    created_at bigint NOT NULL
+);
+
+
+ALTER TABLE public.post_likes OWNER TO postgres;
+
+--
+-- Name: post_mentions; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.post_mentions (
+    post_id integer NOT NULL,
+    user_id integer NOT NULL
+);
+
+
+ALTER TABLE public.post_mentions OWNER TO postgres;
+
+--
+-- Name: post_revision_attachments; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.post_revision_attachments (
+    revision_id integer NOT NULL,
+    "position" integer NOT NULL,
+    media_id character varying(64) NOT NULL,
+    alt_text character varying(1000)
+);
+
+
+ALTER TABLE public.post_revision_attachments OWNER TO postgres;
+
+--
+-- Name: post_revisions; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.post_revisions (
+    revision_id integer NOT NULL,
     post_id integer NOT NULL,
+    text character varying(255),
+    image_id character varying(64),
     unix_time bigint NOT NULL,
-    commentscount integer DEFAULT 0 NOT NULL,
-    comments integer[],
-    edited boolean DEFAULT false NOT NULL
+    content_warning character varying(100),
+    sensitive boolean DEFAULT false NOT NULL
 );
 
 
-ALTER TABLE public.posts OWNER TO postgres;
+ALTER TABLE public.post_revisions OWNER TO postgres;
+
+--
+-- Name: post_revisions_revision_id_seq; Type: SEQUENCE; Schema: public; Owner: postgres
+--
+
+CREATE SEQUENCE public.post_revisions_revision_id_seq
+    AS integer
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+
+ALTER SEQUENCE public.post_revisions_revision_id_seq OWNER TO postgres;
+
+--
+-- Name: post_revisions_revision_id_seq; Type: SEQUENCE OWNED BY; Schema: public; Owner: postgres
+--
+
+ALTER SEQUENCE public.post_revisions_revision_id_seq OWNED BY public.post_revisions.revision_id;
+
 
 --
 -- Name: posts_post_id_seq; Type: SEQUENCE; Schema: public; Owner: postgres
@@ -104,6 +489,43 @@ ALTER SEQUENCE public.posts_post_id_seq OWNER TO postgres;
 ALTER SEQUENCE public.posts_post_id_seq OWNED BY public.posts.post_id;
 
 
+--
+-- Name: scheduled_posts; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.scheduled_posts (
+    scheduled_id integer NOT NULL,
+    owner_id integer NOT NULL,
+    post_data text NOT NULL,
+    publish_at bigint NOT NULL,
+    failed boolean DEFAULT false NOT NULL
+);
+
+
+ALTER TABLE public.scheduled_posts OWNER TO postgres;
+
+--
+-- Name: scheduled_posts_scheduled_id_seq; Type: SEQUENCE; Schema: public; Owner: postgres
+--
+
+CREATE SEQUENCE public.scheduled_posts_scheduled_id_seq
+    AS integer
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+
+ALTER SEQUENCE public.scheduled_posts_scheduled_id_seq OWNER TO postgres;
+
+--
+-- Name: scheduled_posts_scheduled_id_seq; Type: SEQUENCE OWNED BY; Schema: public; Owner: postgres
+--
+
+ALTER SEQUENCE public.scheduled_posts_scheduled_id_seq OWNED BY public.scheduled_posts.scheduled_id;
+
+
 --
 -- Name: users; Type: TABLE; Schema: public; Owner: postgres
 --
@@ -116,10 +538,12 @@ CREATE TABLE public.users (
     id integer NOT NULL,
     followingcount integer NOT NULL,
     followerscount integer NOT NULL,
-    followers integer[],
-    following integer[],
-    icon bytea,
-    bio character varying(255)
+    icon_id character varying(64),
+    bio character varying(255),
+    pinned_post_id integer,
+    sensitive_media character varying(8) DEFAULT 'blur'::character varying NOT NULL,
+    private boolean DEFAULT false NOT NULL,
+    CONSTRAINT users_sensitive_media_check CHECK (((sensitive_media)::text = ANY ((ARRAY['blur'::character varying, 'hide'::character varying])::text[])))
 );
 
 
@@ -155,10 +579,38 @@ ALTER TABLE ONLY public.comments ALTER COLUMN post_id SET DEFAULT nextval('publi
 
 
 --
--- Name: posts post_id; Type: DEFAULT; Schema: public; Owner: postgres
+-- Name: drafts draft_id; Type: DEFAULT; Schema: public; Owner: postgres
 --
 
-ALTER TABLE ONLY public.posts ALTER COLUMN post_id SET DEFAULT nextval('public.posts_post_id_seq'::regclass);
+ALTER TABLE ONLY public.drafts ALTER COLUMN draft_id SET DEFAULT nextval('public.drafts_draft_id_seq'::regclass);
+
+
+--
+-- Name: muted_words word_id; Type: DEFAULT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.muted_words ALTER COLUMN word_id SET DEFAULT nextval('public.muted_words_word_id_seq'::regclass);
+
+
+--
+-- Name: post_revisions revision_id; Type: DEFAULT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_revisions ALTER COLUMN revision_id SET DEFAULT nextval('public.post_revisions_revision_id_seq'::regclass);
+
+
+--
+-- Name: posts post_id; Type: DEFAULT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.posts ALTER COLUMN post_id SET DEFAULT nextval('public.posts_post_id_seq'::regclass);
+
+
+--
+-- Name: scheduled_posts scheduled_id; Type: DEFAULT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.scheduled_posts ALTER COLUMN scheduled_id SET DEFAULT nextval('public.scheduled_posts_scheduled_id_seq'::regclass);
 
 
 --
@@ -168,6 +620,190 @@ ALTER TABLE ONLY public.posts ALTER COLUMN post_id SET DEFAULT nextval('public.p
 ALTER TABLE ONLY public.users ALTER COLUMN id SET DEFAULT nextval('public.users_id_seq'::regclass);
 
 
+--
+-- Name: blocks blocks_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.blocks
+    ADD CONSTRAINT blocks_pkey PRIMARY KEY (blocker_id, blocked_id);
+
+
+--
+-- Name: bookmarks bookmarks_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.bookmarks
+    ADD CONSTRAINT bookmarks_pkey PRIMARY KEY (user_id, post_id);
+
+
+--
+-- Name: comment_attachments comment_attachments_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.comment_attachments
+    ADD CONSTRAINT comment_attachments_pkey PRIMARY KEY (comment_id, "position");
+
+
+--
+-- Name: comment_likes comment_likes_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.comment_likes
+    ADD CONSTRAINT comment_likes_pkey PRIMARY KEY (comment_id, user_id);
+
+
+--
+-- Name: comments comments_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.comments
+    ADD CONSTRAINT comments_pkey PRIMARY KEY (post_id);
+
+
+--
+-- Name: dismissed_suggestions dismissed_suggestions_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.dismissed_suggestions
+    ADD CONSTRAINT dismissed_suggestions_pkey PRIMARY KEY (user_id, dismissed_id);
+
+
+--
+-- Name: drafts drafts_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.drafts
+    ADD CONSTRAINT drafts_pkey PRIMARY KEY (draft_id);
+
+
+--
+-- Name: follow_requests follow_requests_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.follow_requests
+    ADD CONSTRAINT follow_requests_pkey PRIMARY KEY (requester_id, target_id);
+
+
+--
+-- Name: follows follows_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.follows
+    ADD CONSTRAINT follows_pkey PRIMARY KEY (follower_id, followee_id);
+
+
+--
+-- Name: link_previews link_previews_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.link_previews
+    ADD CONSTRAINT link_previews_pkey PRIMARY KEY (url);
+
+
+--
+-- Name: media media_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.media
+    ADD CONSTRAINT media_pkey PRIMARY KEY (media_id);
+
+
+--
+-- Name: media_variants media_variants_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.media_variants
+    ADD CONSTRAINT media_variants_pkey PRIMARY KEY (media_id, name);
+
+
+--
+-- Name: muted_words muted_words_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.muted_words
+    ADD CONSTRAINT muted_words_pkey PRIMARY KEY (word_id);
+
+
+--
+-- Name: muted_words muted_words_user_id_phrase_key; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.muted_words
+    ADD CONSTRAINT muted_words_user_id_phrase_key UNIQUE (user_id, phrase);
+
+
+--
+-- Name: mutes mutes_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.mutes
+    ADD CONSTRAINT mutes_pkey PRIMARY KEY (muter_id, muted_id);
+
+
+--
+-- Name: poll_options poll_options_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.poll_options
+    ADD CONSTRAINT poll_options_pkey PRIMARY KEY (post_id, option_index);
+
+
+--
+-- Name: poll_votes poll_votes_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.poll_votes
+    ADD CONSTRAINT poll_votes_pkey PRIMARY KEY (post_id, user_id);
+
+
+--
+-- Name: polls polls_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.polls
+    ADD CONSTRAINT polls_pkey PRIMARY KEY (post_id);
+
+
+--
+-- Name: post_attachments post_attachments_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_attachments
+    ADD CONSTRAINT post_attachments_pkey PRIMARY KEY (post_id, "position");
+
+
+--
+-- Name: post_likes post_likes_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_likes
+    ADD CONSTRAINT post_likes_pkey PRIMARY KEY (post_id, user_id);
+
+
+--
+-- Name: post_mentions post_mentions_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_mentions
+    ADD CONSTRAINT post_mentions_pkey PRIMARY KEY (post_id, user_id);
+
+
+--
+-- Name: post_revision_attachments post_revision_attachments_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_revision_attachments
+    ADD CONSTRAINT post_revision_attachments_pkey PRIMARY KEY (revision_id, "position");
+
+
+--
+-- Name: post_revisions post_revisions_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_revisions
+    ADD CONSTRAINT post_revisions_pkey PRIMARY KEY (revision_id);
+
+
 --
 -- Name: posts posts_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
 --
@@ -176,6 +812,14 @@ ALTER TABLE ONLY public.posts
     ADD CONSTRAINT posts_pkey PRIMARY KEY (post_id);
 
 
+--
+-- Name: scheduled_posts scheduled_posts_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.scheduled_posts
+    ADD CONSTRAINT scheduled_posts_pkey PRIMARY KEY (scheduled_id);
+
+
 --
 -- Name: users unique_userat; Type: CONSTRAINT; Schema: public; Owner: postgres
 --
@@ -192,6 +836,356 @@ ALTER TABLE ONLY public.users
     ADD CONSTRAINT users_pkey PRIMARY KEY (id);
 
 
+--
+-- Name: blocks_blocked_id_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX blocks_blocked_id_idx ON public.blocks USING btree (blocked_id);
+
+
+--
+-- Name: comment_likes_comment_id_created_at_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX comment_likes_comment_id_created_at_idx ON public.comment_likes USING btree (comment_id, created_at);
+
+
+--
+-- Name: follow_requests_target_id_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX follow_requests_target_id_idx ON public.follow_requests USING btree (target_id, created_at);
+
+
+--
+-- Name: follows_followee_id_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX follows_followee_id_idx ON public.follows USING btree (followee_id, created_at);
+
+
+--
+-- Name: link_previews_status_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX link_previews_status_idx ON public.link_previews USING btree (status);
+
+
+--
+-- Name: post_likes_post_id_created_at_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX post_likes_post_id_created_at_idx ON public.post_likes USING btree (post_id, created_at);
+
+
+--
+-- Name: posts_owner_id_unix_time_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX posts_owner_id_unix_time_idx ON public.posts USING btree (owner_id, unix_time);
+
+
+--
+-- Name: posts_search_vector_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX posts_search_vector_idx ON public.posts USING gin (search_vector);
+
+
+--
+-- Name: posts_unix_time_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX posts_unix_time_idx ON public.posts USING btree (unix_time);
+
+
+--
+-- Name: scheduled_posts_publish_at_idx; Type: INDEX; Schema: public; Owner: postgres
+--
+
+CREATE INDEX scheduled_posts_publish_at_idx ON public.scheduled_posts USING btree (publish_at);
+
+
+--
+-- Name: blocks fk_blocked_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.blocks
+    ADD CONSTRAINT fk_blocked_id FOREIGN KEY (blocked_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: blocks fk_blocker_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.blocks
+    ADD CONSTRAINT fk_blocker_id FOREIGN KEY (blocker_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: bookmarks fk_bookmark_post_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.bookmarks
+    ADD CONSTRAINT fk_bookmark_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+
+--
+-- Name: bookmarks fk_bookmark_user_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.bookmarks
+    ADD CONSTRAINT fk_bookmark_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: comment_attachments fk_attachment_comment_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.comment_attachments
+    ADD CONSTRAINT fk_attachment_comment_id FOREIGN KEY (comment_id) REFERENCES public.comments(post_id) ON DELETE CASCADE;
+
+
+--
+-- Name: comment_attachments fk_attachment_media_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.comment_attachments
+    ADD CONSTRAINT fk_attachment_media_id FOREIGN KEY (media_id) REFERENCES public.media(media_id);
+
+
+--
+-- Name: comment_likes fk_comment_like_comment_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.comment_likes
+    ADD CONSTRAINT fk_comment_like_comment_id FOREIGN KEY (comment_id) REFERENCES public.comments(post_id) ON DELETE CASCADE;
+
+
+--
+-- Name: comment_likes fk_comment_like_user_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.comment_likes
+    ADD CONSTRAINT fk_comment_like_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: comments fk_comment_image_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.comments
+    ADD CONSTRAINT fk_comment_image_id FOREIGN KEY (image_id) REFERENCES public.media(media_id);
+
+
+--
+-- Name: dismissed_suggestions fk_dismissed_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.dismissed_suggestions
+    ADD CONSTRAINT fk_dismissed_id FOREIGN KEY (dismissed_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: dismissed_suggestions fk_dismissed_user_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.dismissed_suggestions
+    ADD CONSTRAINT fk_dismissed_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: drafts fk_draft_owner_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.drafts
+    ADD CONSTRAINT fk_draft_owner_id FOREIGN KEY (owner_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: follow_requests fk_follow_request_requester_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.follow_requests
+    ADD CONSTRAINT fk_follow_request_requester_id FOREIGN KEY (requester_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: follow_requests fk_follow_request_target_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.follow_requests
+    ADD CONSTRAINT fk_follow_request_target_id FOREIGN KEY (target_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: follows fk_followee_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.follows
+    ADD CONSTRAINT fk_followee_id FOREIGN KEY (followee_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: follows fk_follower_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.follows
+    ADD CONSTRAINT fk_follower_id FOREIGN KEY (follower_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: media_variants fk_variant_media_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.media_variants
+    ADD CONSTRAINT fk_variant_media_id FOREIGN KEY (media_id) REFERENCES public.media(media_id) ON DELETE CASCADE;
+
+
+--
+-- Name: media_variants fk_variant_variant_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.media_variants
+    ADD CONSTRAINT fk_variant_variant_id FOREIGN KEY (variant_id) REFERENCES public.media(media_id);
+
+
+--
+-- Name: muted_words fk_muted_word_user_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.muted_words
+    ADD CONSTRAINT fk_muted_word_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: mutes fk_muted_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.mutes
+    ADD CONSTRAINT fk_muted_id FOREIGN KEY (muted_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: mutes fk_muter_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.mutes
+    ADD CONSTRAINT fk_muter_id FOREIGN KEY (muter_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: poll_options fk_poll_option_post_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.poll_options
+    ADD CONSTRAINT fk_poll_option_post_id FOREIGN KEY (post_id) REFERENCES public.polls(post_id) ON DELETE CASCADE;
+
+
+--
+-- Name: poll_votes fk_poll_vote_option; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.poll_votes
+    ADD CONSTRAINT fk_poll_vote_option FOREIGN KEY (post_id, option_index) REFERENCES public.poll_options(post_id, option_index) ON DELETE CASCADE;
+
+
+--
+-- Name: poll_votes fk_poll_vote_user_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.poll_votes
+    ADD CONSTRAINT fk_poll_vote_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: polls fk_poll_post_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.polls
+    ADD CONSTRAINT fk_poll_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+
+--
+-- Name: post_attachments fk_attachment_post_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_attachments
+    ADD CONSTRAINT fk_attachment_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+
+--
+-- Name: post_attachments fk_attachment_media_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_attachments
+    ADD CONSTRAINT fk_attachment_media_id FOREIGN KEY (media_id) REFERENCES public.media(media_id);
+
+
+--
+-- Name: post_likes fk_post_like_post_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_likes
+    ADD CONSTRAINT fk_post_like_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+
+--
+-- Name: post_likes fk_post_like_user_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_likes
+    ADD CONSTRAINT fk_post_like_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: post_mentions fk_mention_post_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_mentions
+    ADD CONSTRAINT fk_mention_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+
+--
+-- Name: post_mentions fk_mention_user_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_mentions
+    ADD CONSTRAINT fk_mention_user_id FOREIGN KEY (user_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: post_revision_attachments fk_revision_attachment_revision_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_revision_attachments
+    ADD CONSTRAINT fk_revision_attachment_revision_id FOREIGN KEY (revision_id) REFERENCES public.post_revisions(revision_id) ON DELETE CASCADE;
+
+
+--
+-- Name: post_revision_attachments fk_revision_attachment_media_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_revision_attachments
+    ADD CONSTRAINT fk_revision_attachment_media_id FOREIGN KEY (media_id) REFERENCES public.media(media_id);
+
+
+--
+-- Name: post_revisions fk_revision_post_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_revisions
+    ADD CONSTRAINT fk_revision_post_id FOREIGN KEY (post_id) REFERENCES public.posts(post_id) ON DELETE CASCADE;
+
+
+--
+-- Name: post_revisions fk_revision_image_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.post_revisions
+    ADD CONSTRAINT fk_revision_image_id FOREIGN KEY (image_id) REFERENCES public.media(media_id);
+
+
 --
 -- Name: posts fk_owner_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
 --
@@ -200,6 +1194,38 @@ ALTER TABLE ONLY public.posts
     ADD CONSTRAINT fk_owner_id FOREIGN KEY (owner_id) REFERENCES public.users(id);
 
 
+--
+-- Name: posts fk_image_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.posts
+    ADD CONSTRAINT fk_image_id FOREIGN KEY (image_id) REFERENCES public.media(media_id);
+
+
+--
+-- Name: scheduled_posts fk_scheduled_owner_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.scheduled_posts
+    ADD CONSTRAINT fk_scheduled_owner_id FOREIGN KEY (owner_id) REFERENCES public.users(id) ON DELETE CASCADE;
+
+
+--
+-- Name: users fk_icon_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.users
+    ADD CONSTRAINT fk_icon_id FOREIGN KEY (icon_id) REFERENCES public.media(media_id);
+
+
+--
+-- Name: users fk_pinned_post_id; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.users
+    ADD CONSTRAINT fk_pinned_post_id FOREIGN KEY (pinned_post_id) REFERENCES public.posts(post_id) ON DELETE SET NULL;
+
+
 --
 -- PostgreSQL database dump complete
 --
@@ -51,6 +51,7 @@ pub async fn create_jwt(claims: Sub) -> Result<Cookie<'static>, Custom<&'static
     }
 }
 
+#[allow(clippy::needless_borrow)]
 pub async fn validate_jwt(jwt: &str) -> Result<Sub, ()> {
     let jwt_secret = dotenv::var("SECRET_JWT_KEY").expect("SECRET_JWT_KEY not found");
 
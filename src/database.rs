@@ -1,13 +1,21 @@
-use std::{env::var, time::Duration};
+use std::{env::var, sync::LazyLock, time::Duration};
 
+use regex::Regex;
 use rocket::{form::validate::Contains, http::Status, response::status::Custom};
 use serde::{Deserialize, Serialize};
-use sqlx::{postgres::PgPoolOptions, prelude::FromRow, query, query_as, Error, Pool, Postgres};
+use sqlx::{
+    postgres::PgPoolOptions, prelude::FromRow, query, query_as, Connection, Error, PgConnection,
+    Pool, Postgres,
+};
 use user::User;
 
 use crate::{
     auth::{hash::compare_password, Sub},
-    routes::{change::EditPostData, types::ClientUser},
+    routes::{
+        change::EditPostData,
+        types::ClientUser,
+        user::{AttachmentData, PostData, SensitiveMedia},
+    },
 };
 
 pub mod user;
@@ -111,11 +119,6 @@ pub async fn verify_password(email: &str, password: &str, pool: &Pool<Postgres>)
     }
 }
 
-pub struct FollowingDBData {
-    followers: Option<Vec<i32>>,
-    following: Option<Vec<i32>>,
-}
-
 pub async fn get_email_from_id(id: &i32, pool: &Pool<Postgres>) -> Result<String, Error> {
     let email = sqlx::query!("SELECT email FROM users WHERE id = $1", id)
         .fetch_one(pool)
@@ -126,28 +129,40 @@ pub async fn get_email_from_id(id: &i32, pool: &Pool<Postgres>) -> Result<String
 
 pub async fn delete_user(email: &str, pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
     let delete_req_id = get_id_from_email(email, pool).await?;
-    let unfollow_data = sqlx::query_as!(
-        FollowingDBData,
-        "SELECT following, followers FROM users WHERE email = $1",
-        email
+    let mut tx = pool.begin().await?;
+
+    // the follows go with the user, the counters of the other side are
+    // updated first
+    sqlx::query!(
+        "UPDATE users SET followerscount = followerscount - 1 WHERE id IN (SELECT followee_id FROM follows WHERE follower_id = $1)",
+        delete_req_id
     )
-    .fetch_one(pool)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE users SET followingcount = followingcount - 1 WHERE id IN (SELECT follower_id FROM follows WHERE followee_id = $1)",
+        delete_req_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    // same for the likes and the counters of what was liked
+    sqlx::query!(
+        "UPDATE posts SET likescount = likescount - 1 WHERE post_id IN (SELECT post_id FROM post_likes WHERE user_id = $1)",
+        delete_req_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE comments SET likescount = likescount - 1 WHERE post_id IN (SELECT comment_id FROM comment_likes WHERE user_id = $1)",
+        delete_req_id
+    )
+    .execute(&mut *tx)
     .await?;
-    if unfollow_data.followers.is_some() {
-        let followers = unfollow_data.followers.unwrap();
-        for id in followers {
-            unfollow_user(&delete_req_id, &id, pool).await?;
-        }
-    }
-    if unfollow_data.following.is_some() {
-        let following = unfollow_data.following.unwrap();
-        for id in following {
-            unfollow_user(&id, &delete_req_id, pool).await?;
-        }
-    }
     sqlx::query!("DELETE FROM users WHERE email = $1", email)
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
+
+    tx.commit().await?;
     Ok(())
 }
 
@@ -155,67 +170,78 @@ pub async fn delete_user(email: &str, pool: &Pool<Postgres>) -> Result<(), sqlx:
 pub struct FollowData {
     pub userat: String,
     pub username: String,
-    pub icon: Option<Vec<u8>>,
+    pub icon_id: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct FollowListEntry {
+    pub userat: String,
+    pub username: String,
+    pub icon_id: Option<String>,
+    /// Whether the viewer follows this user.
+    pub is_following: bool,
+    pub followed_at: i64,
+    pub user_id: i32,
 }
 
+/// Newest follows first. `cursor` is the `followed_at` and user id of the last
+/// entry of the previous page, users blocked either way with the viewer are
+/// left out.
 pub async fn get_following_list(
     email: &str,
+    viewer_id: &Option<i32>,
+    limit: &i64,
+    cursor: &Option<(i64, i32)>,
     pool: &Pool<Postgres>,
-) -> Result<Vec<FollowData>, Error> {
-    let res = sqlx::query!("SELECT following FROM users WHERE email = $1", email)
-        .fetch_one(pool)
-        .await?;
-
-    let mut vec: Vec<FollowData> = vec![];
-
-    let Some(v) = res.following else {
-        return Ok(Vec::new());
-    };
-
-    for u in v {
-        let res = sqlx::query_as!(
-            FollowData,
-            "SELECT userat, username, icon FROM users WHERE id = $1",
-            u
-        )
-        .fetch_one(pool)
-        .await;
-        if let Ok(v) = res {
-            vec.push(v);
-        };
-    }
-
-    Ok(vec)
+) -> Result<Vec<FollowListEntry>, Error> {
+    let res = sqlx::query_as!(
+        FollowListEntry,
+        "SELECT u.userat, u.username, u.icon_id, f.created_at AS followed_at, u.id AS user_id,
+        EXISTS(SELECT 1 FROM follows v WHERE v.follower_id = $2 AND v.followee_id = u.id) AS \"is_following!\"
+        FROM follows f
+        JOIN users u ON u.id = f.followee_id
+        WHERE f.follower_id = (SELECT id FROM users WHERE email = $1)
+        AND ($4::bigint IS NULL OR (f.created_at, u.id) < ($4, $5::integer))
+        AND NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = $2 AND bl.blocked_id = u.id) OR (bl.blocker_id = u.id AND bl.blocked_id = $2))
+        ORDER BY f.created_at DESC, u.id DESC LIMIT $3",
+        email,
+        *viewer_id,
+        limit,
+        cursor.map(|(time, _)| time),
+        cursor.map(|(_, user_id)| user_id)
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
 }
 
+/// Newest followers first, paginated and filtered like [`get_following_list`].
 pub async fn get_followers_list(
     email: &str,
+    viewer_id: &Option<i32>,
+    limit: &i64,
+    cursor: &Option<(i64, i32)>,
     pool: &Pool<Postgres>,
-) -> Result<Vec<FollowData>, Error> {
-    let res = sqlx::query!("SELECT followers FROM users WHERE email = $1", email)
-        .fetch_one(pool)
-        .await?;
-
-    let mut vec: Vec<FollowData> = vec![];
-
-    let Some(v) = res.followers else {
-        return Ok(Vec::new());
-    };
-
-    for u in v {
-        let res = sqlx::query_as!(
-            FollowData,
-            "SELECT userat, username, icon FROM users WHERE id = $1",
-            u
-        )
-        .fetch_one(pool)
-        .await;
-        if let Ok(v) = res {
-            vec.push(v);
-        };
-    }
-
-    Ok(vec)
+) -> Result<Vec<FollowListEntry>, Error> {
+    let res = sqlx::query_as!(
+        FollowListEntry,
+        "SELECT u.userat, u.username, u.icon_id, f.created_at AS followed_at, u.id AS user_id,
+        EXISTS(SELECT 1 FROM follows v WHERE v.follower_id = $2 AND v.followee_id = u.id) AS \"is_following!\"
+        FROM follows f
+        JOIN users u ON u.id = f.follower_id
+        WHERE f.followee_id = (SELECT id FROM users WHERE email = $1)
+        AND ($4::bigint IS NULL OR (f.created_at, u.id) < ($4, $5::integer))
+        AND NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = $2 AND bl.blocked_id = u.id) OR (bl.blocker_id = u.id AND bl.blocked_id = $2))
+        ORDER BY f.created_at DESC, u.id DESC LIMIT $3",
+        email,
+        *viewer_id,
+        limit,
+        cursor.map(|(time, _)| time),
+        cursor.map(|(_, user_id)| user_id)
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
 }
 
 pub async fn user_has_credentials(sub: &Sub, pool: &Pool<Postgres>) -> bool {
@@ -265,7 +291,7 @@ pub async fn make_jwt_claims(
 pub async fn get_client_data(email: &str, pool: &Pool<Postgres>) -> Result<ClientUser, ()> {
     let result = sqlx::query_as!(
         ClientUser,
-        "SELECT username, userat, followingcount, followerscount, icon, bio FROM users WHERE email = $1",
+        "SELECT username, userat, followingcount, followerscount, icon_id, bio, private FROM users WHERE email = $1",
         email
     )
     .fetch_one(pool)
@@ -323,357 +349,1984 @@ pub async fn is_following(
     pool: &Pool<Postgres>,
 ) -> Result<bool, Error> {
     let res = sqlx::query!(
-        "SELECT followers FROM users WHERE id = $1",
+        "SELECT EXISTS(SELECT 1 FROM follows WHERE follower_id = $1 AND followee_id = $2) AS \"exists!\"",
+        follow_suspect_id,
         possibly_followed_owner_id,
     )
     .fetch_one(pool)
     .await?;
-    let Some(followers) = res.followers else {
-        return Ok(false);
-    };
-    if followers.contains(follow_suspect_id) {
-        let res = sqlx::query!(
-            "SELECT following FROM users WHERE id = $1",
-            follow_suspect_id,
-        )
-        .fetch_one(pool)
-        .await?;
-        let Some(following) = res.following else {
-            return Ok(false);
-        };
-        if following.contains(possibly_followed_owner_id) {
-            return Ok(true);
-        }
-    }
+    Ok(res.exists)
+}
+
+#[derive(Debug)]
+pub struct FollowedByPreview {
+    /// How many of the people the viewer follows follow the user.
+    pub count: i64,
+    /// The `limit` of them who followed the user most recently.
+    pub users: Vec<FollowData>,
+}
 
-    Ok(false)
+/// The people the viewer follows who also follow the user, for "followed by"
+/// on profiles.
+pub async fn get_followed_by_preview(
+    viewer_id: &i32,
+    user_id: &i32,
+    limit: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<FollowedByPreview, Error> {
+    let res = sqlx::query!(
+        "SELECT u.userat, u.username, u.icon_id, COUNT(*) OVER () AS \"total!\" FROM follows mine
+        JOIN follows f ON f.follower_id = mine.followee_id
+        JOIN users u ON u.id = f.follower_id
+        WHERE mine.follower_id = $1 AND f.followee_id = $2
+        ORDER BY f.created_at DESC
+        LIMIT $3",
+        viewer_id,
+        user_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(FollowedByPreview {
+        count: res.first().map_or(0, |r| r.total),
+        users: res
+            .into_iter()
+            .map(|r| FollowData {
+                userat: r.userat,
+                username: r.username,
+                icon_id: r.icon_id,
+            })
+            .collect(),
+    })
 }
 
+/// Following someone already followed, or someone blocked either way, does
+/// nothing, the counters only move when the follow is actually added.
 pub async fn follow_user(
     target_id: &i32,
     following_id: &i32,
+    unix_time: &i64,
     pool: &Pool<Postgres>,
 ) -> Result<(), Error> {
-    sqlx::query!(
-        "UPDATE users SET followers = array_append(followers, $2), followerscount = followerscount + 1 WHERE id = $1",
-        target_id,
-        following_id
-
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query!(
-        "UPDATE users SET following = array_append(following, $2), followingcount = followingcount + 1 WHERE id = $1",
-        following_id,
-        target_id,
+    let mut tx = pool.begin().await?;
+    insert_follow(target_id, following_id, unix_time, &mut tx).await?;
+    tx.commit().await?;
+    Ok(())
+}
 
+/// Adds the follow and moves the counters, returns `false` if it already
+/// existed or the users blocked each other.
+async fn insert_follow(
+    followee_id: &i32,
+    follower_id: &i32,
+    unix_time: &i64,
+    conn: &mut PgConnection,
+) -> Result<bool, Error> {
+    let inserted = sqlx::query!(
+        "INSERT INTO follows (follower_id, followee_id, created_at)
+        SELECT $1, $2, $3 WHERE NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = $1 AND bl.blocked_id = $2) OR (bl.blocker_id = $2 AND bl.blocked_id = $1))
+        ON CONFLICT DO NOTHING",
+        follower_id,
+        followee_id,
+        unix_time
     )
-    .execute(pool)
-    .await?;
-
-    Ok(())
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+    if inserted > 0 {
+        update_follow_counts(followee_id, follower_id, 1, conn).await?;
+    }
+    Ok(inserted > 0)
 }
 
+/// Unfollowing someone not followed does nothing.
 pub async fn unfollow_user(
     target_id: &i32,
     unfollowing_id: &i32,
     pool: &Pool<Postgres>,
 ) -> Result<(), Error> {
-    sqlx::query!(
-        "UPDATE users SET followers = array_remove(followers, $2), followerscount = followerscount - 1 WHERE id = $1",
-        target_id,
-        unfollowing_id
+    let mut tx = pool.begin().await?;
 
+    let deleted = sqlx::query!(
+        "DELETE FROM follows WHERE follower_id = $1 AND followee_id = $2",
+        unfollowing_id,
+        target_id
     )
-    .execute(pool)
-    .await?;
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if deleted > 0 {
+        update_follow_counts(target_id, unfollowing_id, -1, &mut tx).await?;
+    }
 
-    sqlx::query!(
-        "UPDATE users SET following = array_remove(following, $2), followingcount = followingcount - 1 WHERE id = $1",
-        unfollowing_id,
-        target_id,
+    tx.commit().await?;
+    Ok(())
+}
 
+/// Both users are updated by one statement, so two users following each
+/// other at the same time can't lock their rows in opposite orders.
+async fn update_follow_counts(
+    followee_id: &i32,
+    follower_id: &i32,
+    delta: i32,
+    conn: &mut PgConnection,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE users SET
+            followerscount = followerscount + CASE WHEN id = $1 THEN $3 ELSE 0 END,
+            followingcount = followingcount + CASE WHEN id = $2 THEN $3 ELSE 0 END
+        WHERE id IN ($1, $2)",
+        followee_id,
+        follower_id,
+        delta
     )
-    .execute(pool)
+    .execute(conn)
     .await?;
-
     Ok(())
 }
 
-pub async fn change_bio(email: &str, bio: &str, pool: &Pool<Postgres>) -> Result<(), Error> {
-    let old_bio = sqlx::query!("SELECT bio FROM users WHERE email = $1", email)
+pub async fn is_private(user_id: &i32, pool: &Pool<Postgres>) -> Result<bool, Error> {
+    let res = sqlx::query!("SELECT private FROM users WHERE id = $1", user_id)
         .fetch_one(pool)
         .await?;
-    let Some(b) = old_bio.bio else {
-        sqlx::query!("UPDATE users SET bio = $1 WHERE email = $2", bio, email)
-            .execute(pool)
-            .await?;
-        return Ok(());
-    };
-    if b != bio {
-        sqlx::query!("UPDATE users SET bio = $1 WHERE email = $2", bio, email)
-            .execute(pool)
-            .await?;
-        return Ok(());
-    }
-
-    Ok(())
+    Ok(res.private)
 }
 
-pub async fn change_username(
-    email: &str,
-    username: &str,
+/// Making the account public approves every pending follow request in the
+/// same transaction.
+pub async fn change_private(
+    user_id: &i32,
+    private: &bool,
+    unix_time: &i64,
     pool: &Pool<Postgres>,
 ) -> Result<(), Error> {
-    let old_name = sqlx::query!("SELECT username FROM users WHERE email = $1", email)
-        .fetch_one(pool)
-        .await?;
-    if old_name.username != username {
-        sqlx::query!(
-            "UPDATE users SET username = $1 WHERE email = $2",
-            username,
-            email
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "UPDATE users SET private = $2 WHERE id = $1",
+        user_id,
+        private
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if !private {
+        let requests = sqlx::query!(
+            "DELETE FROM follow_requests WHERE target_id = $1 RETURNING requester_id",
+            user_id
         )
-        .execute(pool)
+        .fetch_all(&mut *tx)
         .await?;
-        return Ok(());
+        for r in requests {
+            insert_follow(user_id, &r.requester_id, unix_time, &mut tx).await?;
+        }
     }
 
+    tx.commit().await?;
     Ok(())
 }
 
-pub async fn change_icon(email: &str, icon: &str, pool: &Pool<Postgres>) -> Result<(), Error> {
+/// Asking again while a request is pending, or asking someone blocked either
+/// way, does nothing.
+pub async fn request_follow(
+    target_id: &i32,
+    requester_id: &i32,
+    unix_time: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<(), Error> {
     sqlx::query!(
-        "UPDATE users SET icon = $1 WHERE email = $2",
-        Some(icon.as_bytes()),
-        email
+        "INSERT INTO follow_requests (requester_id, target_id, created_at)
+        SELECT $1, $2, $3 WHERE NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = $1 AND bl.blocked_id = $2) OR (bl.blocker_id = $2 AND bl.blocked_id = $1))
+        ON CONFLICT DO NOTHING",
+        requester_id,
+        target_id,
+        unix_time
     )
     .execute(pool)
     .await?;
     Ok(())
 }
 
-pub async fn comment(
-    owner_id: &i32,
-    text: &str,
-    image: &Option<String>,
-    unix_time: &i64,
+/// Removes the request without following, returns `false` if there wasn't
+/// one. Used both by the requester to cancel it and by the target to reject
+/// it.
+pub async fn remove_follow_request(
+    target_id: &i32,
+    requester_id: &i32,
     pool: &Pool<Postgres>,
-    owner_post_id: &i32,
-) -> Result<(), Error> {
-    if image.is_none() {
-        sqlx::query!(
-            "INSERT INTO comments (owner_id, likescount, text, unix_time, owner_post_id) VALUES ($1,$2,$3,$4,$5)",
-            owner_id,
-            0,
-            text,
-            unix_time,
-            owner_post_id
-        )
-        .execute(pool)
-        .await?;
-    } else {
-        sqlx::query!(
-            "INSERT INTO comments (owner_id, likescount, text, image, unix_time, owner_post_id) VALUES ($1,$2,$3,$4,$5,$6)",
-            owner_id,
-            0,
-            text,
-            image.as_ref().unwrap().as_bytes(),
-            unix_time,
-            owner_post_id
-        )
-        .execute(pool)
-        .await?;
-    }
-    sqlx::query!(
-        "UPDATE posts SET commentscount = commentscount + 1 WHERE post_id = $1",
-        owner_post_id
+) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "DELETE FROM follow_requests WHERE requester_id = $1 AND target_id = $2",
+        requester_id,
+        target_id
     )
     .execute(pool)
     .await?;
-
-    Ok(())
+    Ok(res.rows_affected() > 0)
 }
 
-pub async fn post(
-    owner_id: &i32,
-    text: &str,
-    image: &Option<String>,
+/// Turns the request into a follow in one transaction, returns `false` if
+/// there wasn't one.
+pub async fn approve_follow_request(
+    target_id: &i32,
+    requester_id: &i32,
     unix_time: &i64,
     pool: &Pool<Postgres>,
-) -> Result<(), Error> {
-    if image.is_none() {
-        sqlx::query!(
-            "INSERT INTO posts (owner_id, likescount, text, unix_time) VALUES ($1,$2,$3,$4)",
-            owner_id,
-            0,
-            text,
-            unix_time,
-        )
-        .execute(pool)
-        .await?;
-    } else {
-        sqlx::query!(
-            "INSERT INTO posts (owner_id, likescount, text, image, unix_time) VALUES ($1,$2,$3,$4,$5)",
-            owner_id,
-            0,
-            text,
-            image.as_ref().unwrap().as_bytes(),
-            unix_time,
-        )
-        .execute(pool)
-        .await?;
-    }
+) -> Result<bool, Error> {
+    let mut tx = pool.begin().await?;
 
-    Ok(())
-}
+    let removed = sqlx::query!(
+        "DELETE FROM follow_requests WHERE requester_id = $1 AND target_id = $2",
+        requester_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if removed == 0 {
+        return Ok(false);
+    }
+    insert_follow(target_id, requester_id, unix_time, &mut tx).await?;
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Post {
-    pub text: Option<String>,
-    pub image: Option<Vec<u8>>,
-    pub owner_id: i32,
-    pub likescount: i32,
-    pub commentscount: i32,
-    pub unix_time: i64,
-    pub post_id: i32,
-    pub edited: bool,
+    tx.commit().await?;
+    Ok(true)
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Comment {
-    pub text: Option<String>,
-    pub image: Option<Vec<u8>>,
-    pub owner_id: i32,
-    pub likescount: i32,
-    pub commentscount: i32,
-    pub unix_time: i64,
-    pub post_id: i32,
+pub async fn has_requested_follow(
+    target_id: &i32,
+    requester_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "SELECT EXISTS(SELECT 1 FROM follow_requests WHERE requester_id = $1 AND target_id = $2) AS \"exists!\"",
+        requester_id,
+        target_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(res.exists)
 }
 
-pub async fn get_posts(pool: &Pool<Postgres>) -> Result<Vec<Post>, Error> {
+/// Users waiting for the target to answer, newest request first.
+pub async fn get_incoming_follow_requests(
+    target_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<FollowData>, Error> {
     let res = sqlx::query_as!(
-        Post,
-        "SELECT text, image, owner_id, post_id, likescount, commentscount, unix_time, edited FROM posts ORDER BY unix_time DESC"
+        FollowData,
+        "SELECT u.userat, u.username, u.icon_id FROM follow_requests r
+        JOIN users u ON u.id = r.requester_id
+        WHERE r.target_id = $1
+        ORDER BY r.created_at DESC",
+        target_id
     )
     .fetch_all(pool)
     .await?;
     Ok(res)
 }
 
-pub async fn get_comments_from_post(
+/// Users the requester is waiting on, newest request first.
+pub async fn get_outgoing_follow_requests(
+    requester_id: &i32,
     pool: &Pool<Postgres>,
-    post_id: &i32,
-) -> Result<Vec<Comment>, Error> {
+) -> Result<Vec<FollowData>, Error> {
     let res = sqlx::query_as!(
-        Comment,
-        "SELECT text, image, owner_id, post_id, likescount, commentscount, unix_time FROM comments WHERE owner_post_id = $1 ORDER BY unix_time DESC",
-        post_id
+        FollowData,
+        "SELECT u.userat, u.username, u.icon_id FROM follow_requests r
+        JOIN users u ON u.id = r.target_id
+        WHERE r.requester_id = $1
+        ORDER BY r.created_at DESC",
+        requester_id
     )
     .fetch_all(pool)
     .await?;
     Ok(res)
 }
 
-pub async fn get_post_by_id(pool: &Pool<Postgres>, post_id: &i32) -> Result<Post, Error> {
-    let res = sqlx::query_as!(
-        Post,
-        "SELECT text, edited, image, owner_id, post_id, likescount, commentscount, unix_time FROM posts WHERE post_id = $1",
-        post_id
+/// Whether either user blocked the other.
+pub async fn is_blocked(
+    user_id: &i32,
+    other_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "SELECT EXISTS(SELECT 1 FROM blocks WHERE (blocker_id = $1 AND blocked_id = $2) OR (blocker_id = $2 AND blocked_id = $1)) AS \"exists!\"",
+        user_id,
+        other_id
     )
     .fetch_one(pool)
     .await?;
-    Ok(res)
+    Ok(res.exists)
 }
 
-pub async fn get_user_posts(pool: &Pool<Postgres>, owner_id: &i32) -> Result<Vec<Post>, Error> {
-    let res = sqlx::query_as!(
-        Post,
-        "SELECT text, image, edited, owner_id, post_id, likescount, commentscount, unix_time FROM posts WHERE owner_id = $1 ORDER BY unix_time DESC",
-        owner_id
+pub async fn has_blocked(
+    blocker_id: &i32,
+    blocked_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "SELECT EXISTS(SELECT 1 FROM blocks WHERE blocker_id = $1 AND blocked_id = $2) AS \"exists!\"",
+        blocker_id,
+        blocked_id
     )
-    .fetch_all(pool)
+    .fetch_one(pool)
     .await?;
-    Ok(res)
+    Ok(res.exists)
 }
 
-pub async fn like_comment(
+/// Blocking someone already blocked does nothing. The follows and follow
+/// requests between both users are removed in the same transaction, in both
+/// directions.
+pub async fn block_user(
+    blocker_id: &i32,
+    blocked_id: &i32,
+    unix_time: &i64,
     pool: &Pool<Postgres>,
-    owner_id: &i32,
-    post_id: &i32,
 ) -> Result<(), Error> {
-    sqlx::query!("UPDATE comments SET likescount = likescount + 1, likes = array_append(likes, $2) WHERE post_id = $1", post_id,owner_id).execute(pool).await?;
-    Ok(())
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO blocks (blocker_id, blocked_id, created_at) VALUES ($1,$2,$3) ON CONFLICT DO NOTHING",
+        blocker_id,
+        blocked_id,
+        unix_time
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let removed = sqlx::query!(
+        "DELETE FROM follows WHERE (follower_id = $1 AND followee_id = $2) OR (follower_id = $2 AND followee_id = $1) RETURNING follower_id, followee_id",
+        blocker_id,
+        blocked_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for f in removed {
+        update_follow_counts(&f.followee_id, &f.follower_id, -1, &mut tx).await?;
+    }
+    sqlx::query!(
+        "DELETE FROM follow_requests WHERE (requester_id = $1 AND target_id = $2) OR (requester_id = $2 AND target_id = $1)",
+        blocker_id,
+        blocked_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Unblocking someone not blocked does nothing, the removed follows aren't
+/// restored.
+pub async fn unblock_user(
+    blocker_id: &i32,
+    blocked_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "DELETE FROM blocks WHERE blocker_id = $1 AND blocked_id = $2",
+        blocker_id,
+        blocked_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Newest blocks first.
+pub async fn get_blocked_list(
+    blocker_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<FollowData>, Error> {
+    let res = sqlx::query_as!(
+        FollowData,
+        "SELECT u.userat, u.username, u.icon_id FROM blocks bl
+        JOIN users u ON u.id = bl.blocked_id
+        WHERE bl.blocker_id = $1
+        ORDER BY bl.created_at DESC",
+        blocker_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Muting someone already muted does nothing.
+pub async fn mute_user(
+    muter_id: &i32,
+    muted_id: &i32,
+    unix_time: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO mutes (muter_id, muted_id, created_at) VALUES ($1,$2,$3) ON CONFLICT DO NOTHING",
+        muter_id,
+        muted_id,
+        unix_time
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn unmute_user(
+    muter_id: &i32,
+    muted_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "DELETE FROM mutes WHERE muter_id = $1 AND muted_id = $2",
+        muter_id,
+        muted_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn has_muted(
+    muter_id: &i32,
+    muted_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "SELECT EXISTS(SELECT 1 FROM mutes WHERE muter_id = $1 AND muted_id = $2) AS \"exists!\"",
+        muter_id,
+        muted_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(res.exists)
+}
+
+/// Newest mutes first.
+pub async fn get_muted_list(
+    muter_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<FollowData>, Error> {
+    let res = sqlx::query_as!(
+        FollowData,
+        "SELECT u.userat, u.username, u.icon_id FROM mutes mu
+        JOIN users u ON u.id = mu.muted_id
+        WHERE mu.muter_id = $1
+        ORDER BY mu.created_at DESC",
+        muter_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+#[derive(Debug)]
+pub struct MutedWord {
+    pub word_id: i32,
+    /// Lowercased, with its whitespace collapsed.
+    pub phrase: String,
+    pub expires_at: Option<i64>,
+}
+
+/// Muting a phrase that is already muted replaces its expiry. Returns `None`
+/// if the user already has `max_words` phrases that haven't expired and this
+/// one isn't one of them.
+pub async fn add_muted_word(
+    user_id: &i32,
+    phrase: &str,
+    expires_at: &Option<i64>,
+    unix_time: &i64,
+    max_words: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<Option<MutedWord>, Error> {
+    let res = sqlx::query_as!(
+        MutedWord,
+        "INSERT INTO muted_words (user_id, phrase, expires_at, created_at)
+        SELECT $1, $2::text, $3, $4 WHERE (SELECT COUNT(*) FROM muted_words WHERE user_id = $1 AND (expires_at IS NULL OR expires_at > $4)) < $5
+            OR EXISTS(SELECT 1 FROM muted_words WHERE user_id = $1 AND phrase = $2 AND (expires_at IS NULL OR expires_at > $4))
+        ON CONFLICT (user_id, phrase) DO UPDATE SET expires_at = EXCLUDED.expires_at
+        RETURNING word_id, phrase, expires_at",
+        user_id,
+        phrase,
+        *expires_at,
+        unix_time,
+        max_words
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(res)
+}
+
+/// The phrases that haven't expired yet, oldest first.
+pub async fn get_muted_words(
+    user_id: &i32,
+    now: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<MutedWord>, Error> {
+    let res = sqlx::query_as!(
+        MutedWord,
+        "SELECT word_id, phrase, expires_at FROM muted_words
+        WHERE user_id = $1 AND (expires_at IS NULL OR expires_at > $2)
+        ORDER BY created_at ASC",
+        user_id,
+        now
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Returns `false` if the phrase doesn't exist or belongs to someone else.
+pub async fn delete_muted_word(
+    word_id: &i32,
+    user_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "DELETE FROM muted_words WHERE word_id = $1 AND user_id = $2",
+        word_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Ranks accounts the user might want to follow by how many of the people they
+/// follow follow them, how many of the user's followers follow them, and how
+/// much they posted since `since`. Accounts active since `since` are also
+/// candidates so new users without follows still get suggestions. Skips the
+/// user, the accounts they follow or asked to follow, blocks either way and
+/// dismissed suggestions.
+pub async fn get_suggested_user_ids(
+    user_id: &i32,
+    since: &i64,
+    friends_weight: &f64,
+    followers_weight: &f64,
+    activity_weight: &f64,
+    limit: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<i32>, Error> {
+    let res = sqlx::query!(
+        "WITH following AS (
+            SELECT followee_id AS id FROM follows WHERE follower_id = $1
+        ), friends AS (
+            SELECT f.followee_id AS id, COUNT(*) AS n FROM follows f
+            JOIN following fo ON fo.id = f.follower_id
+            GROUP BY f.followee_id
+        ), shared_followers AS (
+            SELECT f.followee_id AS id, COUNT(*) AS n FROM follows mine
+            JOIN follows f ON f.follower_id = mine.follower_id
+            WHERE mine.followee_id = $1
+            GROUP BY f.followee_id
+        ), active AS (
+            SELECT id FROM (
+                SELECT owner_id AS id FROM posts WHERE unix_time >= $2 AND visibility = 'public'
+                GROUP BY owner_id ORDER BY COUNT(*) DESC LIMIT $6
+            ) a
+        ), candidates AS (
+            SELECT id FROM friends UNION SELECT id FROM shared_followers UNION SELECT id FROM active
+        )
+        SELECT c.id AS \"id!\" FROM candidates c
+        LEFT JOIN friends fr ON fr.id = c.id
+        LEFT JOIN shared_followers sf ON sf.id = c.id
+        WHERE c.id <> $1
+        AND NOT EXISTS(SELECT 1 FROM following fo WHERE fo.id = c.id)
+        AND NOT EXISTS(SELECT 1 FROM follow_requests r WHERE r.requester_id = $1 AND r.target_id = c.id)
+        AND NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = $1 AND bl.blocked_id = c.id) OR (bl.blocker_id = c.id AND bl.blocked_id = $1))
+        AND NOT EXISTS(SELECT 1 FROM dismissed_suggestions d WHERE d.user_id = $1 AND d.dismissed_id = c.id)
+        ORDER BY COALESCE(fr.n, 0) * $3::float8
+            + COALESCE(sf.n, 0) * $4::float8
+            + ln(1 + (SELECT COUNT(*) FROM posts p WHERE p.owner_id = c.id AND p.unix_time >= $2)) * $5::float8 DESC,
+            c.id
+        LIMIT $6",
+        user_id,
+        since,
+        friends_weight,
+        followers_weight,
+        activity_weight,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res.into_iter().map(|r| r.id).collect())
+}
+
+/// Keeps the order of `user_ids` but drops the accounts the user followed,
+/// asked to follow, blocked, got blocked by or dismissed since they were
+/// ranked.
+pub async fn filter_suggested_users(
+    user_id: &i32,
+    user_ids: &[i32],
+    limit: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<FollowData>, Error> {
+    let res = sqlx::query_as!(
+        FollowData,
+        "SELECT u.userat, u.username, u.icon_id FROM unnest($2::int4[]) WITH ORDINALITY AS s(id, n)
+        JOIN users u ON u.id = s.id
+        WHERE NOT EXISTS(SELECT 1 FROM follows f WHERE f.follower_id = $1 AND f.followee_id = u.id)
+        AND NOT EXISTS(SELECT 1 FROM follow_requests r WHERE r.requester_id = $1 AND r.target_id = u.id)
+        AND NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = $1 AND bl.blocked_id = u.id) OR (bl.blocker_id = u.id AND bl.blocked_id = $1))
+        AND NOT EXISTS(SELECT 1 FROM dismissed_suggestions d WHERE d.user_id = $1 AND d.dismissed_id = u.id)
+        ORDER BY s.n
+        LIMIT $3",
+        user_id,
+        user_ids,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Dismissing someone already dismissed does nothing.
+pub async fn dismiss_suggestion(
+    user_id: &i32,
+    dismissed_id: &i32,
+    unix_time: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO dismissed_suggestions (user_id, dismissed_id, created_at) VALUES ($1,$2,$3) ON CONFLICT DO NOTHING",
+        user_id,
+        dismissed_id,
+        unix_time
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn change_bio(email: &str, bio: &str, pool: &Pool<Postgres>) -> Result<(), Error> {
+    let old_bio = sqlx::query!("SELECT bio FROM users WHERE email = $1", email)
+        .fetch_one(pool)
+        .await?;
+    let Some(b) = old_bio.bio else {
+        sqlx::query!("UPDATE users SET bio = $1 WHERE email = $2", bio, email)
+            .execute(pool)
+            .await?;
+        return Ok(());
+    };
+    if b != bio {
+        sqlx::query!("UPDATE users SET bio = $1 WHERE email = $2", bio, email)
+            .execute(pool)
+            .await?;
+        return Ok(());
+    }
+
+    Ok(())
+}
+
+pub async fn change_username(
+    email: &str,
+    username: &str,
+    pool: &Pool<Postgres>,
+) -> Result<(), Error> {
+    let old_name = sqlx::query!("SELECT username FROM users WHERE email = $1", email)
+        .fetch_one(pool)
+        .await?;
+    if old_name.username != username {
+        sqlx::query!(
+            "UPDATE users SET username = $1 WHERE email = $2",
+            username,
+            email
+        )
+        .execute(pool)
+        .await?;
+        return Ok(());
+    }
+
+    Ok(())
+}
+
+pub async fn change_icon(
+    email: &str,
+    icon_id: &Option<String>,
+    pool: &Pool<Postgres>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE users SET icon_id = $1 WHERE email = $2",
+        *icon_id,
+        email
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn comment(
+    owner_id: &i32,
+    text: &str,
+    image_id: &Option<String>,
+    attachments: &[AttachmentData],
+    unix_time: &i64,
+    pool: &Pool<Postgres>,
+    owner_post_id: &i32,
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+    let res = sqlx::query!(
+        "INSERT INTO comments (owner_id, likescount, text, image_id, unix_time, owner_post_id) VALUES ($1,$2,$3,$4,$5,$6) RETURNING post_id",
+        owner_id,
+        0,
+        text,
+        *image_id,
+        unix_time,
+        owner_post_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    insert_comment_attachments(&res.post_id, attachments, &mut tx).await?;
+    sqlx::query!(
+        "UPDATE posts SET commentscount = commentscount + 1 WHERE post_id = $1",
+        owner_post_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Attachment {
+    pub media_id: String,
+    pub alt_text: Option<String>,
+    pub position: i32,
+    pub width: i32,
+    pub height: i32,
+    pub mime_type: String,
+    pub blurhash: Option<String>,
+    pub duration_ms: Option<i32>,
+    pub thumbnail_id: Option<String>,
+    pub preview_id: Option<String>,
+    pub poster_id: Option<String>,
+}
+
+/// Replaces the attachments of the post, their position is their index.
+async fn insert_post_attachments(
+    post_id: &i32,
+    attachments: &[AttachmentData],
+    conn: &mut PgConnection,
+) -> Result<(), Error> {
+    sqlx::query!("DELETE FROM post_attachments WHERE post_id = $1", post_id)
+        .execute(&mut *conn)
+        .await?;
+    for (i, a) in attachments.iter().enumerate() {
+        sqlx::query!(
+            "INSERT INTO post_attachments (post_id, position, media_id, alt_text) VALUES ($1,$2,$3,$4)",
+            post_id,
+            i as i32,
+            a.data,
+            a.alt_text
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn insert_comment_attachments(
+    comment_id: &i32,
+    attachments: &[AttachmentData],
+    conn: &mut PgConnection,
+) -> Result<(), Error> {
+    for (i, a) in attachments.iter().enumerate() {
+        sqlx::query!(
+            "INSERT INTO comment_attachments (comment_id, position, media_id, alt_text) VALUES ($1,$2,$3,$4)",
+            comment_id,
+            i as i32,
+            a.data,
+            a.alt_text
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn get_post_attachments(
+    pool: &Pool<Postgres>,
+    post_id: &i32,
+) -> Result<Vec<Attachment>, Error> {
+    let res = sqlx::query_as!(
+        Attachment,
+        "SELECT a.media_id, a.alt_text, a.position, COALESCE(m.width, 0) AS \"width!\", COALESCE(m.height, 0) AS \"height!\", m.mime_type, m.blurhash, m.duration_ms, (SELECT v.variant_id FROM media_variants v WHERE v.media_id = a.media_id AND v.name = 'thumbnail') AS thumbnail_id, (SELECT v.variant_id FROM media_variants v WHERE v.media_id = a.media_id AND v.name = 'preview') AS preview_id, (SELECT v.variant_id FROM media_variants v WHERE v.media_id = a.media_id AND v.name = 'poster') AS poster_id FROM post_attachments a JOIN media m ON m.media_id = a.media_id WHERE a.post_id = $1 ORDER BY a.position",
+        post_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+pub async fn get_comment_attachments(
+    pool: &Pool<Postgres>,
+    comment_id: &i32,
+) -> Result<Vec<Attachment>, Error> {
+    let res = sqlx::query_as!(
+        Attachment,
+        "SELECT a.media_id, a.alt_text, a.position, COALESCE(m.width, 0) AS \"width!\", COALESCE(m.height, 0) AS \"height!\", m.mime_type, m.blurhash, m.duration_ms, (SELECT v.variant_id FROM media_variants v WHERE v.media_id = a.media_id AND v.name = 'thumbnail') AS thumbnail_id, (SELECT v.variant_id FROM media_variants v WHERE v.media_id = a.media_id AND v.name = 'preview') AS preview_id, (SELECT v.variant_id FROM media_variants v WHERE v.media_id = a.media_id AND v.name = 'poster') AS poster_id FROM comment_attachments a JOIN media m ON m.media_id = a.media_id WHERE a.comment_id = $1 ORDER BY a.position",
+        comment_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+pub async fn post(
+    owner_id: &i32,
+    post_data: &PostData,
+    unix_time: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<i32, Error> {
+    let mut tx = pool.begin().await?;
+    let post_id = insert_post(owner_id, post_data, unix_time, &mut tx).await?;
+    tx.commit().await?;
+    Ok(post_id)
+}
+
+/// Inserts a post using an existing connection so it can be part of a larger
+/// transaction, returns the id of the new post.
+async fn insert_post(
+    owner_id: &i32,
+    post_data: &PostData,
+    unix_time: &i64,
+    conn: &mut PgConnection,
+) -> Result<i32, Error> {
+    let text = post_data.text.clone().unwrap_or_default();
+    let res = sqlx::query!(
+        "INSERT INTO posts (owner_id, likescount, text, image_id, unix_time, visibility, content_warning, sensitive) VALUES ($1,$2,$3,$4,$5,$6,$7,$8) RETURNING post_id",
+        owner_id,
+        0,
+        text,
+        post_data.image,
+        unix_time,
+        post_data.visibility.as_str(),
+        post_data.content_warning,
+        post_data.sensitive
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    insert_mentions(&res.post_id, &text, conn).await?;
+    queue_link_previews(&text, unix_time, conn).await?;
+    insert_post_attachments(&res.post_id, &post_data.attachments, conn).await?;
+
+    if let Some(poll) = &post_data.poll {
+        sqlx::query!(
+            "INSERT INTO polls (post_id, closes_at) VALUES ($1,$2)",
+            res.post_id,
+            poll.closes_at
+        )
+        .execute(&mut *conn)
+        .await?;
+        for (i, option) in poll.options.iter().enumerate() {
+            sqlx::query!(
+                "INSERT INTO poll_options (post_id, option_index, text) VALUES ($1,$2,$3)",
+                res.post_id,
+                i as i32,
+                option.trim()
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+
+    Ok(res.post_id)
+}
+
+/// Returns the lowercased `user_at`s mentioned as `@user_at` in the text.
+pub fn parse_mentions(text: &str) -> Vec<String> {
+    static MENTION_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"@(\w+)").unwrap());
+    let mut mentions: Vec<String> = vec![];
+    for c in MENTION_REGEX.captures_iter(text) {
+        let user_at = c[1].to_lowercase();
+        if !mentions.contains(&user_at) {
+            mentions.push(user_at);
+        }
+    }
+    mentions
+}
+
+/// Replaces the mentions stored for the post with the ones found in `text`,
+/// users blocked either way by the author aren't mentioned.
+async fn insert_mentions(post_id: &i32, text: &str, conn: &mut PgConnection) -> Result<(), Error> {
+    sqlx::query!("DELETE FROM post_mentions WHERE post_id = $1", post_id)
+        .execute(&mut *conn)
+        .await?;
+    let mentions = parse_mentions(text);
+    if mentions.is_empty() {
+        return Ok(());
+    }
+    sqlx::query!(
+        "INSERT INTO post_mentions (post_id, user_id)
+        SELECT p.post_id, u.id FROM posts p JOIN users u ON u.userat = ANY($2)
+        WHERE p.post_id = $1
+        AND NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = p.owner_id AND bl.blocked_id = u.id) OR (bl.blocker_id = u.id AND bl.blocked_id = p.owner_id))
+        ON CONFLICT DO NOTHING",
+        post_id,
+        &mentions
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Returns the distinct `http(s)` URLs found in the text, in order.
+pub fn parse_urls(text: &str) -> Vec<String> {
+    const MAX_URLS: usize = 4;
+    static URL_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"https?://[^\s<>"']+"#).unwrap());
+    let mut urls: Vec<String> = vec![];
+    for m in URL_REGEX.find_iter(text) {
+        // trailing punctuation is almost always part of the sentence
+        let url = m
+            .as_str()
+            .trim_end_matches(['.', ',', '!', '?', ')', ';', ':']);
+        if !urls.iter().any(|u| u == url) {
+            urls.push(url.to_string());
+        }
+        if urls.len() == MAX_URLS {
+            break;
+        }
+    }
+    urls
+}
+
+/// Queues the URLs found in `text` for the link preview worker, URLs that
+/// already have a preview are left as they are.
+async fn queue_link_previews(
+    text: &str,
+    unix_time: &i64,
+    conn: &mut PgConnection,
+) -> Result<(), Error> {
+    let urls = parse_urls(text);
+    if urls.is_empty() {
+        return Ok(());
+    }
+    sqlx::query!(
+        "INSERT INTO link_previews (url, updated_at) SELECT unnest($1::text[]), $2 ON CONFLICT DO NOTHING",
+        &urls,
+        unix_time
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    #[serde(rename = "siteName")]
+    pub site_name: Option<String>,
+}
+
+/// Marks up to `limit` queued URLs as being fetched and returns them, URLs
+/// stuck in `fetching` since before `stale_before` are claimed again.
+pub async fn claim_link_previews(
+    now: &i64,
+    stale_before: &i64,
+    limit: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<String>, Error> {
+    let res = sqlx::query!(
+        "UPDATE link_previews SET status = 'fetching', updated_at = $1
+        WHERE url IN (
+            SELECT url FROM link_previews
+            WHERE status = 'pending' OR (status = 'fetching' AND updated_at < $2)
+            LIMIT $3 FOR UPDATE SKIP LOCKED
+        ) RETURNING url",
+        now,
+        stale_before,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res.into_iter().map(|r| r.url).collect())
+}
+
+/// Stores the fetched preview, `None` marks the URL as failed so it isn't
+/// fetched again.
+pub async fn save_link_preview(
+    url: &str,
+    preview: &Option<LinkPreview>,
+    now: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<(), Error> {
+    let preview = preview.clone();
+    let status = if preview.is_some() { "ready" } else { "failed" };
+    let preview = preview.unwrap_or_default();
+    sqlx::query!(
+        "UPDATE link_previews SET status = $2, title = $3, description = $4, image = $5, site_name = $6, updated_at = $7 WHERE url = $1",
+        url,
+        status,
+        preview.title,
+        preview.description,
+        preview.image,
+        preview.site_name,
+        now
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Returns the preview of the first URL in `text` that has one ready.
+pub async fn get_link_preview(
+    pool: &Pool<Postgres>,
+    text: &str,
+) -> Result<Option<LinkPreview>, Error> {
+    let urls = parse_urls(text);
+    if urls.is_empty() {
+        return Ok(None);
+    }
+    let mut previews = sqlx::query_as!(
+        LinkPreview,
+        "SELECT url, title, description, image, site_name FROM link_previews WHERE url = ANY($1) AND status = 'ready'",
+        &urls
+    )
+    .fetch_all(pool)
+    .await?;
+    previews.sort_by_key(|p| urls.iter().position(|u| *u == p.url));
+    Ok(previews.into_iter().next())
+}
+
+pub async fn schedule_post(
+    owner_id: &i32,
+    post_data: &PostData,
+    publish_at: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<(), Error> {
+    let mut conn = pool.acquire().await?;
+    insert_scheduled_post(owner_id, post_data, publish_at, &mut conn).await
+}
+
+async fn insert_scheduled_post(
+    owner_id: &i32,
+    post_data: &PostData,
+    publish_at: &i64,
+    conn: &mut PgConnection,
+) -> Result<(), Error> {
+    let Ok(data) = rocket::serde::json::to_string(post_data) else {
+        return Err(Error::Protocol("unable to serialize post data".to_string()));
+    };
+    sqlx::query!(
+        "INSERT INTO scheduled_posts (owner_id, post_data, publish_at) VALUES ($1,$2,$3)",
+        owner_id,
+        data,
+        publish_at
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Returns whether a scheduled post with this id belonging to the owner was
+/// still pending and got updated.
+pub async fn update_scheduled_post(
+    scheduled_id: &i32,
+    owner_id: &i32,
+    post_data: &PostData,
+    publish_at: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<bool, Error> {
+    let Ok(data) = rocket::serde::json::to_string(post_data) else {
+        return Err(Error::Protocol("unable to serialize post data".to_string()));
+    };
+    let res = sqlx::query!(
+        "UPDATE scheduled_posts SET post_data = $3, publish_at = $4, failed = false WHERE scheduled_id = $1 AND owner_id = $2",
+        scheduled_id,
+        owner_id,
+        data,
+        publish_at
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn cancel_scheduled_post(
+    scheduled_id: &i32,
+    owner_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "DELETE FROM scheduled_posts WHERE scheduled_id = $1 AND owner_id = $2",
+        scheduled_id,
+        owner_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+#[derive(Debug)]
+pub struct ScheduledPost {
+    pub scheduled_id: i32,
+    pub owner_id: i32,
+    pub post_data: String,
+    pub publish_at: i64,
+    /// Set when publishing failed, the post stays until the owner edits or
+    /// cancels it.
+    pub failed: bool,
+}
+
+pub async fn get_scheduled_posts(
+    owner_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<ScheduledPost>, Error> {
+    let res = sqlx::query_as!(
+        ScheduledPost,
+        "SELECT scheduled_id, owner_id, post_data, publish_at, failed FROM scheduled_posts WHERE owner_id = $1 ORDER BY publish_at ASC",
+        owner_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Publishes every scheduled post that is due. Rows are locked with `SKIP
+/// LOCKED` and removed in the same transaction the post is created in, so
+/// several server instances can run this at the same time without publishing
+/// a post twice. Posts that can't be published are marked failed instead of
+/// being retried forever, their owner still sees them.
+pub async fn publish_due_posts(now: &i64, pool: &Pool<Postgres>) -> Result<usize, Error> {
+    const BATCH_SIZE: i64 = 50;
+    let mut tx = pool.begin().await?;
+
+    let due = sqlx::query_as!(
+        ScheduledPost,
+        "SELECT scheduled_id, owner_id, post_data, publish_at, failed FROM scheduled_posts WHERE publish_at <= $1 AND NOT failed ORDER BY publish_at ASC LIMIT $2 FOR UPDATE SKIP LOCKED",
+        now,
+        BATCH_SIZE
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for s in &due {
+        let published = match rocket::serde::json::from_str::<PostData>(&s.post_data) {
+            Ok(post_data) => {
+                // a failed insert only rolls back this post
+                let mut savepoint = tx.begin().await?;
+                match insert_post(&s.owner_id, &post_data, &s.publish_at, &mut savepoint).await {
+                    Ok(..) => savepoint.commit().await.map_err(|e| e.to_string()),
+                    Err(e) => {
+                        savepoint.rollback().await?;
+                        Err(e.to_string())
+                    }
+                }
+            }
+            Err(e) => Err(e.to_string()),
+        };
+
+        match published {
+            Ok(..) => {
+                sqlx::query!(
+                    "DELETE FROM scheduled_posts WHERE scheduled_id = $1",
+                    s.scheduled_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            Err(e) => {
+                error!("unable to publish scheduled post {}: {e}", s.scheduled_id);
+                sqlx::query!(
+                    "UPDATE scheduled_posts SET failed = true WHERE scheduled_id = $1",
+                    s.scheduled_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+    }
+
+    tx.commit().await?;
+    Ok(due.len())
+}
+
+/// Anonymous viewers get the default.
+pub async fn get_sensitive_media(
+    pool: &Pool<Postgres>,
+    viewer_id: &Option<i32>,
+) -> Result<SensitiveMedia, Error> {
+    let Some(viewer_id) = viewer_id else {
+        return Ok(SensitiveMedia::default());
+    };
+    let res = sqlx::query!("SELECT sensitive_media FROM users WHERE id = $1", viewer_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(SensitiveMedia::from_db(&res.sensitive_media))
+}
+
+pub async fn change_sensitive_media(
+    user_id: &i32,
+    sensitive_media: &SensitiveMedia,
+    pool: &Pool<Postgres>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE users SET sensitive_media = $2 WHERE id = $1",
+        user_id,
+        sensitive_media.as_str()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_pinned_post_id(
+    user_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<Option<i32>, Error> {
+    let res = sqlx::query!("SELECT pinned_post_id FROM users WHERE id = $1", user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(res.pinned_post_id)
+}
+
+/// Pins one of the user's own posts, returns `false` if the post doesn't
+/// belong to them.
+pub async fn pin_post(user_id: &i32, post_id: &i32, pool: &Pool<Postgres>) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "UPDATE users SET pinned_post_id = $2 WHERE id = $1 AND EXISTS(SELECT 1 FROM posts WHERE post_id = $2 AND owner_id = $1)",
+        user_id,
+        post_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn unpin_post(user_id: &i32, pool: &Pool<Postgres>) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE users SET pinned_post_id = NULL WHERE id = $1",
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct PollOption {
+    pub option_index: i32,
+    pub text: String,
+    pub votes: i64,
+}
+
+#[derive(Debug)]
+pub struct Poll {
+    pub closes_at: i64,
+    pub options: Vec<PollOption>,
+    /// The option the viewer voted for, if any.
+    pub voted_option: Option<i32>,
+}
+
+pub async fn get_poll(
+    pool: &Pool<Postgres>,
+    post_id: &i32,
+    viewer_id: &Option<i32>,
+) -> Result<Option<Poll>, Error> {
+    let Some(poll) = sqlx::query!("SELECT closes_at FROM polls WHERE post_id = $1", post_id)
+        .fetch_optional(pool)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let options = sqlx::query_as!(
+        PollOption,
+        "SELECT o.option_index, o.text, COUNT(v.user_id) AS \"votes!\"
+        FROM poll_options o LEFT JOIN poll_votes v ON v.post_id = o.post_id AND v.option_index = o.option_index
+        WHERE o.post_id = $1
+        GROUP BY o.option_index, o.text
+        ORDER BY o.option_index ASC",
+        post_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let voted_option = match viewer_id {
+        Some(id) => sqlx::query!(
+            "SELECT option_index FROM poll_votes WHERE post_id = $1 AND user_id = $2",
+            post_id,
+            id
+        )
+        .fetch_optional(pool)
+        .await?
+        .map(|v| v.option_index),
+        None => None,
+    };
+
+    Ok(Some(Poll {
+        closes_at: poll.closes_at,
+        options,
+        voted_option,
+    }))
+}
+
+/// Returns `false` if the user already voted on this poll.
+pub async fn vote_poll(
+    pool: &Pool<Postgres>,
+    post_id: &i32,
+    user_id: &i32,
+    option_index: &i32,
+    unix_time: &i64,
+) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "INSERT INTO poll_votes (post_id, user_id, option_index, unix_time) VALUES ($1,$2,$3,$4) ON CONFLICT DO NOTHING",
+        post_id,
+        user_id,
+        option_index,
+        unix_time
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+#[derive(Debug)]
+pub struct Draft {
+    pub draft_id: i32,
+    pub post_data: String,
+    pub unix_time: i64,
+}
+
+pub async fn create_draft(
+    owner_id: &i32,
+    post_data: &PostData,
+    unix_time: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<Draft, Error> {
+    let Ok(data) = rocket::serde::json::to_string(post_data) else {
+        return Err(Error::Protocol("unable to serialize post data".to_string()));
+    };
+    let res = sqlx::query_as!(
+        Draft,
+        "INSERT INTO drafts (owner_id, post_data, unix_time) VALUES ($1,$2,$3) RETURNING draft_id, post_data, unix_time",
+        owner_id,
+        data,
+        unix_time
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Returns `None` if the draft doesn't exist or belongs to someone else.
+pub async fn update_draft(
+    draft_id: &i32,
+    owner_id: &i32,
+    post_data: &PostData,
+    unix_time: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<Option<Draft>, Error> {
+    let Ok(data) = rocket::serde::json::to_string(post_data) else {
+        return Err(Error::Protocol("unable to serialize post data".to_string()));
+    };
+    let res = sqlx::query_as!(
+        Draft,
+        "UPDATE drafts SET post_data = $3, unix_time = $4 WHERE draft_id = $1 AND owner_id = $2 RETURNING draft_id, post_data, unix_time",
+        draft_id,
+        owner_id,
+        data,
+        unix_time
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(res)
+}
+
+pub async fn get_drafts(owner_id: &i32, pool: &Pool<Postgres>) -> Result<Vec<Draft>, Error> {
+    let res = sqlx::query_as!(
+        Draft,
+        "SELECT draft_id, post_data, unix_time FROM drafts WHERE owner_id = $1 ORDER BY unix_time DESC",
+        owner_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+pub async fn get_draft(
+    draft_id: &i32,
+    owner_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<Option<Draft>, Error> {
+    let res = sqlx::query_as!(
+        Draft,
+        "SELECT draft_id, post_data, unix_time FROM drafts WHERE draft_id = $1 AND owner_id = $2",
+        draft_id,
+        owner_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(res)
+}
+
+pub async fn delete_draft(
+    draft_id: &i32,
+    owner_id: &i32,
+    pool: &Pool<Postgres>,
+) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "DELETE FROM drafts WHERE draft_id = $1 AND owner_id = $2",
+        draft_id,
+        owner_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Turns the draft into a post, or into a scheduled post if it has a
+/// `publish_at` in the future, and removes the draft in the same transaction.
+/// Returns `false` if the draft doesn't exist or belongs to someone else.
+pub async fn publish_draft(
+    draft_id: &i32,
+    owner_id: &i32,
+    unix_time: &i64,
+    pool: &Pool<Postgres>,
+) -> Result<bool, Error> {
+    let mut tx = pool.begin().await?;
+
+    let Some(draft) = sqlx::query_as!(
+        Draft,
+        "SELECT draft_id, post_data, unix_time FROM drafts WHERE draft_id = $1 AND owner_id = $2 FOR UPDATE",
+        draft_id,
+        owner_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(false);
+    };
+
+    let Ok(post_data) = rocket::serde::json::from_str::<PostData>(&draft.post_data) else {
+        return Err(Error::Protocol(
+            "unable to deserialize post data".to_string(),
+        ));
+    };
+
+    match post_data.publish_at {
+        Some(publish_at) if publish_at > *unix_time => {
+            insert_scheduled_post(owner_id, &post_data, &publish_at, &mut tx).await?;
+        }
+        _ => {
+            insert_post(owner_id, &post_data, unix_time, &mut tx).await?;
+        }
+    }
+
+    sqlx::query!("DELETE FROM drafts WHERE draft_id = $1", draft_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Post {
+    pub text: Option<String>,
+    pub image_id: Option<String>,
+    pub owner_id: i32,
+    pub likescount: i32,
+    pub commentscount: i32,
+    pub unix_time: i64,
+    pub post_id: i32,
+    pub edited: bool,
+    pub edited_at: Option<i64>,
+    pub visibility: String,
+    pub content_warning: Option<String>,
+    pub sensitive: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Comment {
+    pub text: Option<String>,
+    pub image_id: Option<String>,
+    pub owner_id: i32,
+    pub likescount: i32,
+    pub commentscount: i32,
+    pub unix_time: i64,
+    pub post_id: i32,
+}
+
+/// Only returns the posts `viewer_id` is allowed to see, see [`can_view_post`],
+/// and leaves out the posts of users they muted.
+pub async fn get_posts(pool: &Pool<Postgres>, viewer_id: &Option<i32>) -> Result<Vec<Post>, Error> {
+    let res = sqlx::query_as!(
+        Post,
+        "SELECT p.text, p.image_id, p.owner_id AS \"owner_id!\", p.post_id AS \"post_id!\", p.likescount AS \"likescount!\", p.commentscount AS \"commentscount!\", p.unix_time AS \"unix_time!\", p.edited AS \"edited!\", p.edited_at, p.visibility AS \"visibility!\", p.content_warning, p.sensitive AS \"sensitive!\" FROM visible_posts($1) p
+        WHERE NOT EXISTS(SELECT 1 FROM mutes mu WHERE mu.muter_id = $1 AND mu.muted_id = p.owner_id)
+        ORDER BY p.unix_time DESC",
+        *viewer_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// The posts of `post_ids` that `viewer_id` is allowed to see, in the order of
+/// `post_ids`. Deleted posts are left out.
+pub async fn get_visible_posts_by_ids(
+    pool: &Pool<Postgres>,
+    post_ids: &[i32],
+    viewer_id: &Option<i32>,
+) -> Result<Vec<Post>, Error> {
+    let res = sqlx::query_as!(
+        Post,
+        "SELECT p.text, p.image_id, p.owner_id AS \"owner_id!\", p.post_id AS \"post_id!\", p.likescount AS \"likescount!\", p.commentscount AS \"commentscount!\", p.unix_time AS \"unix_time!\", p.edited AS \"edited!\", p.edited_at, p.visibility AS \"visibility!\", p.content_warning, p.sensitive AS \"sensitive!\" FROM visible_posts($1) p
+        WHERE p.post_id = ANY($2)
+        ORDER BY array_position($2, p.post_id)",
+        *viewer_id,
+        post_ids
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Public posts can be seen by anyone, followers-only posts by the author and
+/// their followers, and mentioned-only posts by the author and the users
+/// mentioned in them. Posts of private accounts can only be seen by their
+/// followers, and posts of users blocked either way by the viewer can't be
+/// seen. Returns `false` if the post doesn't exist.
+///
+/// The visibility levels, private accounts and blocks are checked by the
+/// `visible_posts` SQL function, every query returning posts to a viewer reads
+/// them from it. The columns it returns are nullable to sqlx, so the
+/// `NOT NULL` ones are marked with `!`.
+pub async fn can_view_post(
+    pool: &Pool<Postgres>,
+    post_id: &i32,
+    viewer_id: &Option<i32>,
+) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "SELECT EXISTS(SELECT 1 FROM visible_posts($1) p WHERE p.post_id = $2) AS \"exists!\"",
+        *viewer_id,
+        post_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(res.exists)
+}
+
+pub struct PostSearch {
+    /// Already in `to_tsquery` syntax.
+    pub tsquery: Option<String>,
+    pub from: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+/// Full-text search over the posts `viewer_id` is allowed to see, best match
+/// first.
+pub async fn search_posts(
+    pool: &Pool<Postgres>,
+    search: &PostSearch,
+    viewer_id: &Option<i32>,
+    limit: &i64,
+    offset: &i64,
+) -> Result<Vec<Post>, Error> {
+    let res = sqlx::query_as!(
+        Post,
+        "SELECT p.text, p.image_id, p.owner_id AS \"owner_id!\", p.post_id AS \"post_id!\", p.likescount AS \"likescount!\", p.commentscount AS \"commentscount!\", p.unix_time AS \"unix_time!\", p.edited AS \"edited!\", p.edited_at, p.visibility AS \"visibility!\", p.content_warning, p.sensitive AS \"sensitive!\"
+        FROM visible_posts($1) p JOIN users o ON o.id = p.owner_id
+        WHERE ($2::text IS NULL OR p.search_vector @@ to_tsquery('simple', $2))
+        AND ($3::text IS NULL OR o.userat = $3)
+        AND ($4::bigint IS NULL OR p.unix_time >= $4)
+        AND ($5::bigint IS NULL OR p.unix_time <= $5)
+        ORDER BY CASE WHEN $2::text IS NULL THEN 0 ELSE ts_rank(p.search_vector, to_tsquery('simple', $2)) END DESC, p.unix_time DESC
+        LIMIT $6 OFFSET $7",
+        *viewer_id,
+        search.tsquery,
+        search.from,
+        search.since,
+        search.until,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Returns the ids of the posts logged out viewers can see, public posts of
+/// public accounts, newer than `since`, ordered by their weighted likes and
+/// comments decayed by the age of the post in hours.
+pub async fn get_trending_post_ids(
+    pool: &Pool<Postgres>,
+    now: &i64,
+    since: &i64,
+    like_weight: &f64,
+    comment_weight: &f64,
+    gravity: &f64,
+    limit: &i64,
+) -> Result<Vec<i32>, Error> {
+    let res = sqlx::query!(
+        "SELECT p.post_id AS \"post_id!\" FROM visible_posts(NULL) p
+        WHERE p.unix_time >= $2 AND p.unix_time <= $1 AND p.likescount + p.commentscount > 0
+        ORDER BY (p.likescount * $3::float8 + p.commentscount * $4::float8) / power(($1 - p.unix_time) / 3600000.0 + 2, $5::float8) DESC
+        LIMIT $6",
+        now,
+        since,
+        like_weight,
+        comment_weight,
+        gravity,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res.into_iter().map(|r| r.post_id).collect())
+}
+
+#[derive(Debug)]
+pub struct HashtagCount {
+    pub tag: String,
+    pub recent: i64,
+    pub previous: i64,
+}
+
+/// Counts the hashtags used in the posts logged out viewers can see newer than
+/// `since`, split into the ones used after `recent_since` and the ones used
+/// before it.
+pub async fn get_hashtag_counts(
+    pool: &Pool<Postgres>,
+    since: &i64,
+    recent_since: &i64,
+) -> Result<Vec<HashtagCount>, Error> {
+    let res = sqlx::query_as!(
+        HashtagCount,
+        "SELECT lower(m[1]) AS \"tag!\",
+            COUNT(DISTINCT p.post_id) FILTER (WHERE p.unix_time >= $2) AS \"recent!\",
+            COUNT(DISTINCT p.post_id) FILTER (WHERE p.unix_time < $2) AS \"previous!\"
+        FROM visible_posts(NULL) p, regexp_matches(p.text, '#(\\w+)', 'g') AS m
+        WHERE p.unix_time >= $1
+        GROUP BY 1",
+        since,
+        recent_since
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
 }
 
-pub async fn dislike_comment(
+/// Comments of users blocked either way by the viewer, or muted by them, are
+/// left out.
+pub async fn get_comments_from_post(
     pool: &Pool<Postgres>,
-    owner_id: &i32,
     post_id: &i32,
-) -> Result<(), Error> {
-    sqlx::query!("UPDATE comments SET likescount = likescount - 1, likes = array_remove(likes, $2) WHERE post_id = $1", post_id,owner_id).execute(pool).await?;
-    Ok(())
+    viewer_id: &Option<i32>,
+) -> Result<Vec<Comment>, Error> {
+    let res = sqlx::query_as!(
+        Comment,
+        "SELECT c.text, c.image_id, c.owner_id, c.post_id, c.likescount, c.commentscount, c.unix_time FROM comments c
+        WHERE c.owner_post_id = $1
+        AND NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = c.owner_id AND bl.blocked_id = $2) OR (bl.blocker_id = $2 AND bl.blocked_id = c.owner_id))
+        AND NOT EXISTS(SELECT 1 FROM mutes mu WHERE mu.muter_id = $2 AND mu.muted_id = c.owner_id)
+        ORDER BY c.unix_time DESC",
+        post_id,
+        *viewer_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
 }
 
-pub async fn like(pool: &Pool<Postgres>, owner_id: &i32, post_id: &i32) -> Result<(), Error> {
-    sqlx::query!("UPDATE posts SET likescount = likescount + 1, likes = array_append(likes, $2) WHERE post_id = $1", post_id,owner_id).execute(pool).await?;
-    Ok(())
+pub async fn get_post_by_id(pool: &Pool<Postgres>, post_id: &i32) -> Result<Post, Error> {
+    let res = sqlx::query_as!(
+        Post,
+        "SELECT text, edited, edited_at, visibility, content_warning, sensitive, image_id, owner_id, post_id, likescount, commentscount, unix_time FROM posts WHERE post_id = $1",
+        post_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(res)
 }
 
-pub async fn dislike(pool: &Pool<Postgres>, owner_id: &i32, post_id: &i32) -> Result<(), Error> {
-    sqlx::query!("UPDATE posts SET likescount = likescount - 1, likes = array_remove(likes, $2) WHERE post_id = $1", post_id,owner_id).execute(pool).await?;
-    Ok(())
+pub async fn get_user_posts(
+    pool: &Pool<Postgres>,
+    owner_id: &i32,
+    viewer_id: &Option<i32>,
+) -> Result<Vec<Post>, Error> {
+    let res = sqlx::query_as!(
+        Post,
+        "SELECT p.text, p.image_id, p.edited AS \"edited!\", p.edited_at, p.visibility AS \"visibility!\", p.content_warning, p.sensitive AS \"sensitive!\", p.owner_id AS \"owner_id!\", p.post_id AS \"post_id!\", p.likescount AS \"likescount!\", p.commentscount AS \"commentscount!\", p.unix_time AS \"unix_time!\" FROM visible_posts($1) p
+        WHERE p.owner_id = $2
+        ORDER BY p.unix_time DESC",
+        *viewer_id,
+        owner_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Liking a comment already liked does nothing, the count only moves when the
+/// like is actually added. Returns the comment's likes count, `None` if it
+/// doesn't exist.
+pub async fn like_comment(
+    pool: &Pool<Postgres>,
+    user_id: &i32,
+    comment_id: &i32,
+    unix_time: &i64,
+) -> Result<Option<i32>, Error> {
+    let mut tx = pool.begin().await?;
+
+    let added = sqlx::query!(
+        "INSERT INTO comment_likes (comment_id, user_id, created_at)
+        SELECT $1, $2, $3 WHERE EXISTS(SELECT 1 FROM comments WHERE post_id = $1)
+        ON CONFLICT DO NOTHING",
+        comment_id,
+        user_id,
+        unix_time
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let res = sqlx::query!(
+        "UPDATE comments SET likescount = likescount + $2 WHERE post_id = $1 RETURNING likescount",
+        comment_id,
+        added as i32
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(res.map(|r| r.likescount))
+}
+
+/// Unliking a comment that isn't liked does nothing. Returns the comment's
+/// likes count, `None` if it doesn't exist.
+pub async fn dislike_comment(
+    pool: &Pool<Postgres>,
+    user_id: &i32,
+    comment_id: &i32,
+) -> Result<Option<i32>, Error> {
+    let mut tx = pool.begin().await?;
+
+    let removed = sqlx::query!(
+        "DELETE FROM comment_likes WHERE comment_id = $1 AND user_id = $2",
+        comment_id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let res = sqlx::query!(
+        "UPDATE comments SET likescount = likescount - $2 WHERE post_id = $1 RETURNING likescount",
+        comment_id,
+        removed as i32
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(res.map(|r| r.likescount))
+}
+
+/// Same as [`like_comment`] for a post.
+pub async fn like(
+    pool: &Pool<Postgres>,
+    user_id: &i32,
+    post_id: &i32,
+    unix_time: &i64,
+) -> Result<Option<i32>, Error> {
+    let mut tx = pool.begin().await?;
+
+    let added = sqlx::query!(
+        "INSERT INTO post_likes (post_id, user_id, created_at)
+        SELECT $1, $2, $3 WHERE EXISTS(SELECT 1 FROM posts WHERE post_id = $1)
+        ON CONFLICT DO NOTHING",
+        post_id,
+        user_id,
+        unix_time
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let res = sqlx::query!(
+        "UPDATE posts SET likescount = likescount + $2 WHERE post_id = $1 RETURNING likescount",
+        post_id,
+        added as i32
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(res.map(|r| r.likescount))
 }
 
-struct LikesList {
-    likes: Option<Vec<i32>>,
+/// Same as [`dislike_comment`] for a post.
+pub async fn dislike(
+    pool: &Pool<Postgres>,
+    user_id: &i32,
+    post_id: &i32,
+) -> Result<Option<i32>, Error> {
+    let mut tx = pool.begin().await?;
+
+    let removed = sqlx::query!(
+        "DELETE FROM post_likes WHERE post_id = $1 AND user_id = $2",
+        post_id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let res = sqlx::query!(
+        "UPDATE posts SET likescount = likescount - $2 WHERE post_id = $1 RETURNING likescount",
+        post_id,
+        removed as i32
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(res.map(|r| r.likescount))
 }
 
-pub async fn likes_list_contains(
+pub async fn has_liked_post(
     pool: &Pool<Postgres>,
     post_id: &i32,
     user_id: &i32,
 ) -> Result<bool, Error> {
-    let res = query_as!(
-        LikesList,
-        "SELECT likes FROM posts WHERE post_id = $1",
-        post_id
+    let res = sqlx::query!(
+        "SELECT EXISTS(SELECT 1 FROM post_likes WHERE post_id = $1 AND user_id = $2) AS \"exists!\"",
+        post_id,
+        user_id
     )
     .fetch_one(pool)
     .await?;
-    let Some(v) = res.likes else {
-        return Ok(false);
-    };
-    Ok(v.contains(user_id))
+    Ok(res.exists)
 }
 
-pub async fn comment_likes_list_contains(
+pub async fn has_liked_comment(
     pool: &Pool<Postgres>,
-    post_id: &i32,
+    comment_id: &i32,
     user_id: &i32,
 ) -> Result<bool, Error> {
-    let res = query_as!(
-        LikesList,
-        "SELECT likes FROM comments WHERE post_id = $1",
-        post_id
+    let res = sqlx::query!(
+        "SELECT EXISTS(SELECT 1 FROM comment_likes WHERE comment_id = $1 AND user_id = $2) AS \"exists!\"",
+        comment_id,
+        user_id
     )
     .fetch_one(pool)
     .await?;
-    let Some(v) = res.likes else {
+    Ok(res.exists)
+}
+
+#[derive(Debug)]
+pub struct Liker {
+    pub username: String,
+    pub userat: String,
+    pub icon_id: Option<String>,
+    pub is_following: bool,
+}
+
+/// Users who liked the post, most recent like first. Users blocked either way
+/// by the viewer are left out.
+pub async fn get_post_likers(
+    pool: &Pool<Postgres>,
+    post_id: &i32,
+    viewer_id: &Option<i32>,
+    limit: &i64,
+    offset: &i64,
+) -> Result<Vec<Liker>, Error> {
+    let res = sqlx::query_as!(
+        Liker,
+        "SELECT u.username, u.userat, u.icon_id, EXISTS(SELECT 1 FROM follows f WHERE f.follower_id = $2::integer AND f.followee_id = u.id) AS \"is_following!\"
+        FROM post_likes l
+        JOIN users u ON u.id = l.user_id
+        WHERE l.post_id = $1
+        AND NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = u.id AND bl.blocked_id = $2) OR (bl.blocker_id = $2 AND bl.blocked_id = u.id))
+        ORDER BY l.created_at DESC LIMIT $3 OFFSET $4",
+        post_id,
+        *viewer_id,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Same as [`get_post_likers`] for a comment.
+pub async fn get_comment_likers(
+    pool: &Pool<Postgres>,
+    comment_id: &i32,
+    viewer_id: &Option<i32>,
+    limit: &i64,
+    offset: &i64,
+) -> Result<Vec<Liker>, Error> {
+    let res = sqlx::query_as!(
+        Liker,
+        "SELECT u.username, u.userat, u.icon_id, EXISTS(SELECT 1 FROM follows f WHERE f.follower_id = $2::integer AND f.followee_id = u.id) AS \"is_following!\"
+        FROM comment_likes l
+        JOIN users u ON u.id = l.user_id
+        WHERE l.comment_id = $1
+        AND NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = u.id AND bl.blocked_id = $2) OR (bl.blocker_id = $2 AND bl.blocked_id = u.id))
+        ORDER BY l.created_at DESC LIMIT $3 OFFSET $4",
+        comment_id,
+        *viewer_id,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// The post the comment was made on, `None` if the comment doesn't exist.
+pub async fn get_comment_owner_post_id(
+    pool: &Pool<Postgres>,
+    comment_id: &i32,
+) -> Result<Option<i32>, Error> {
+    let res = sqlx::query!(
+        "SELECT owner_post_id FROM comments WHERE post_id = $1",
+        comment_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(res.map(|r| r.owner_post_id))
+}
+
+/// Whoever can see the post a comment was made on can see the comment, unless
+/// the viewer and the author of the comment blocked each other. Returns
+/// `false` if the comment doesn't exist.
+pub async fn can_view_comment(
+    pool: &Pool<Postgres>,
+    comment_id: &i32,
+    viewer_id: &Option<i32>,
+) -> Result<bool, Error> {
+    let Some(owner_post_id) = get_comment_owner_post_id(pool, comment_id).await? else {
         return Ok(false);
     };
-    Ok(v.contains(user_id))
+    if !can_view_post(pool, &owner_post_id, viewer_id).await? {
+        return Ok(false);
+    }
+    let res = sqlx::query!(
+        "SELECT NOT EXISTS(SELECT 1 FROM comments c JOIN blocks bl ON (bl.blocker_id = c.owner_id AND bl.blocked_id = $2) OR (bl.blocker_id = $2 AND bl.blocked_id = c.owner_id) WHERE c.post_id = $1) AS \"visible!\"",
+        comment_id,
+        *viewer_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(res.visible)
 }
 
 #[derive(Debug, PartialEq, Eq, FromRow)]
 pub struct DBUserWithIcon {
     pub username: String,
     pub userat: String,
-    pub icon: Option<Vec<u8>>,
+    pub icon_id: Option<String>,
 }
 
-pub async fn query_like(query: &str, pool: &Pool<Postgres>) -> Result<Vec<DBUserWithIcon>, Error> {
+/// Users blocked either way by the viewer are left out.
+pub async fn query_like(
+    query: &str,
+    viewer_id: &Option<i32>,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<DBUserWithIcon>, Error> {
     //let user_at_query = format!(
     //    "SELECT icon, userat, username FROM users WHERE {} LIKE '{}%'",
     //    "userat", query
@@ -684,15 +2337,19 @@ pub async fn query_like(query: &str, pool: &Pool<Postgres>) -> Result<Vec<DBUser
     //);
     let user_at_res: Vec<DBUserWithIcon> = sqlx::query_as!(
         DBUserWithIcon,
-        "SELECT icon, userat, username FROM users WHERE LOWER(userat) LIKE LOWER($1)",
-        format!("%{query}%")
+        "SELECT icon_id, userat, username FROM users WHERE LOWER(userat) LIKE LOWER($1)
+        AND NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = users.id AND bl.blocked_id = $2) OR (bl.blocker_id = $2 AND bl.blocked_id = users.id))",
+        format!("%{query}%"),
+        *viewer_id
     )
     .fetch_all(pool)
     .await?;
     let mut username_res: Vec<DBUserWithIcon> = sqlx::query_as!(
         DBUserWithIcon,
-        "SELECT icon, userat, username FROM users WHERE LOWER(username) LIKE LOWER($1)",
-        format!("%{query}%")
+        "SELECT icon_id, userat, username FROM users WHERE LOWER(username) LIKE LOWER($1)
+        AND NOT EXISTS(SELECT 1 FROM blocks bl WHERE (bl.blocker_id = users.id AND bl.blocked_id = $2) OR (bl.blocker_id = $2 AND bl.blocked_id = users.id))",
+        format!("%{query}%"),
+        *viewer_id
     )
     .fetch_all(pool)
     .await?;
@@ -730,18 +2387,297 @@ pub async fn delete_post(post_id: &i32, pool: &Pool<Postgres>) -> Result<(), Err
     Ok(())
 }
 
+/// Saves the current content of the post as a revision before overwriting it,
+/// so the history of what was "originally said" is never lost.
 pub async fn edit_post(
     post_id: &i32,
     post_data: &EditPostData,
+    unix_time: &i64,
     pool: &Pool<Postgres>,
 ) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+
+    let current = sqlx::query!(
+        "SELECT text, image_id, unix_time, edited_at, content_warning, sensitive FROM posts WHERE post_id = $1 FOR UPDATE",
+        post_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let revision = sqlx::query!(
+        "INSERT INTO post_revisions (post_id, text, image_id, unix_time, content_warning, sensitive) VALUES ($1,$2,$3,$4,$5,$6) RETURNING revision_id",
+        post_id,
+        current.text,
+        current.image_id,
+        current.edited_at.unwrap_or(current.unix_time),
+        current.content_warning,
+        current.sensitive
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "INSERT INTO post_revision_attachments (revision_id, position, media_id, alt_text)
+        SELECT $1, position, media_id, alt_text FROM post_attachments WHERE post_id = $2",
+        revision.revision_id,
+        post_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
     sqlx::query!(
-        "UPDATE posts SET text = $2, image = $3, edited = true WHERE post_id = $1",
+        "UPDATE posts SET text = $2, image_id = $3, edited = true, edited_at = $4, content_warning = $5, sensitive = $6 WHERE post_id = $1",
         post_id,
         post_data.text,
-        post_data.image.as_bytes()
+        // an empty image removes it
+        (!post_data.image.is_empty()).then_some(&post_data.image),
+        unix_time,
+        post_data.content_warning,
+        post_data.sensitive
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    insert_mentions(post_id, &post_data.text, &mut tx).await?;
+    queue_link_previews(&post_data.text, unix_time, &mut tx).await?;
+    insert_post_attachments(post_id, &post_data.attachments, &mut tx).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PostRevision {
+    pub revision_id: i32,
+    pub text: Option<String>,
+    pub image_id: Option<String>,
+    pub unix_time: i64,
+    pub content_warning: Option<String>,
+    pub sensitive: bool,
+}
+
+/// Returns every previous version of the post, oldest first. The current
+/// version is not included.
+pub async fn get_post_revisions(
+    pool: &Pool<Postgres>,
+    post_id: &i32,
+) -> Result<Vec<PostRevision>, Error> {
+    let res = sqlx::query_as!(
+        PostRevision,
+        "SELECT revision_id, text, image_id, unix_time, content_warning, sensitive FROM post_revisions WHERE post_id = $1 ORDER BY unix_time ASC, revision_id ASC",
+        post_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// The attachments the post had before the edit that saved the revision.
+pub async fn get_post_revision_attachments(
+    pool: &Pool<Postgres>,
+    revision_id: &i32,
+) -> Result<Vec<Attachment>, Error> {
+    let res = sqlx::query_as!(
+        Attachment,
+        "SELECT a.media_id, a.alt_text, a.position, COALESCE(m.width, 0) AS \"width!\", COALESCE(m.height, 0) AS \"height!\", m.mime_type, m.blurhash, m.duration_ms, (SELECT v.variant_id FROM media_variants v WHERE v.media_id = a.media_id AND v.name = 'thumbnail') AS thumbnail_id, (SELECT v.variant_id FROM media_variants v WHERE v.media_id = a.media_id AND v.name = 'preview') AS preview_id, (SELECT v.variant_id FROM media_variants v WHERE v.media_id = a.media_id AND v.name = 'poster') AS poster_id FROM post_revision_attachments a JOIN media m ON m.media_id = a.media_id WHERE a.revision_id = $1 ORDER BY a.position",
+        revision_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+pub async fn bookmark(
+    pool: &Pool<Postgres>,
+    user_id: &i32,
+    post_id: &i32,
+    unix_time: &i64,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO bookmarks (user_id, post_id, unix_time) VALUES ($1,$2,$3) ON CONFLICT DO NOTHING",
+        user_id,
+        post_id,
+        unix_time
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_bookmark(
+    pool: &Pool<Postgres>,
+    user_id: &i32,
+    post_id: &i32,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "DELETE FROM bookmarks WHERE user_id = $1 AND post_id = $2",
+        user_id,
+        post_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn is_bookmarked(
+    pool: &Pool<Postgres>,
+    post_id: &i32,
+    user_id: &i32,
+) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "SELECT EXISTS(SELECT 1 FROM bookmarks WHERE user_id = $1 AND post_id = $2) AS \"exists!\"",
+        user_id,
+        post_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(res.exists)
+}
+
+struct BookmarkedPost {
+    text: Option<String>,
+    image_id: Option<String>,
+    owner_id: i32,
+    likescount: i32,
+    commentscount: i32,
+    unix_time: i64,
+    post_id: i32,
+    edited: bool,
+    edited_at: Option<i64>,
+    visibility: String,
+    content_warning: Option<String>,
+    sensitive: bool,
+    bookmarked_at: i64,
+}
+
+/// Returns `(bookmarked_at, post)` pairs, newest bookmark first. `cursor` is the
+/// `(bookmarked_at, post_id)` of the last bookmark of the previous page, the id
+/// breaks ties between bookmarks made in the same millisecond.
+pub async fn get_bookmarked_posts(
+    pool: &Pool<Postgres>,
+    user_id: &i32,
+    limit: &i64,
+    cursor: &Option<(i64, i32)>,
+) -> Result<Vec<(i64, Post)>, Error> {
+    let res = sqlx::query_as!(
+        BookmarkedPost,
+        "SELECT p.text, p.image_id, p.owner_id AS \"owner_id!\", p.post_id AS \"post_id!\", p.likescount AS \"likescount!\", p.commentscount AS \"commentscount!\", p.unix_time AS \"unix_time!\", p.edited AS \"edited!\", p.edited_at, p.visibility AS \"visibility!\", p.content_warning, p.sensitive AS \"sensitive!\", b.unix_time AS bookmarked_at
+        FROM bookmarks b JOIN visible_posts($1) p ON p.post_id = b.post_id
+        WHERE b.user_id = $1 AND ($2::bigint IS NULL OR (b.unix_time, b.post_id) < ($2, $4::integer))
+        ORDER BY b.unix_time DESC, b.post_id DESC LIMIT $3",
+        user_id,
+        cursor.map(|(time, _)| time),
+        limit,
+        cursor.map(|(_, post_id)| post_id)
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res
+        .into_iter()
+        .map(|b| {
+            (
+                b.bookmarked_at,
+                Post {
+                    text: b.text,
+                    image_id: b.image_id,
+                    owner_id: b.owner_id,
+                    likescount: b.likescount,
+                    commentscount: b.commentscount,
+                    unix_time: b.unix_time,
+                    post_id: b.post_id,
+                    edited: b.edited,
+                    edited_at: b.edited_at,
+                    visibility: b.visibility,
+                    content_warning: b.content_warning,
+                    sensitive: b.sensitive,
+                },
+            )
+        })
+        .collect())
+}
+
+pub async fn media_exists(pool: &Pool<Postgres>, media_id: &str) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "SELECT EXISTS(SELECT 1 FROM media WHERE media_id = $1) AS \"exists!\"",
+        media_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(res.exists)
+}
+
+/// A stored file, `width`, `height` and `blurhash` are only known for
+/// images and videos, `duration_ms` for GIFs and videos.
+pub struct Media {
+    pub media_id: String,
+    pub mime_type: String,
+    pub size: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub blurhash: Option<String>,
+    pub duration_ms: Option<i32>,
+}
+
+pub async fn insert_media(
+    pool: &Pool<Postgres>,
+    media: &Media,
+    unix_time: &i64,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO media (media_id, mime_type, size, width, height, blurhash, duration_ms, unix_time) VALUES ($1,$2,$3,$4,$5,$6,$7,$8) ON CONFLICT DO NOTHING",
+        media.media_id,
+        media.mime_type,
+        media.size,
+        media.width,
+        media.height,
+        media.blurhash,
+        media.duration_ms,
+        unix_time
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn insert_media_variant(
+    pool: &Pool<Postgres>,
+    media_id: &str,
+    name: &str,
+    variant_id: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO media_variants (media_id, name, variant_id) VALUES ($1,$2,$3) ON CONFLICT DO NOTHING",
+        media_id,
+        name,
+        variant_id
     )
     .execute(pool)
     .await?;
     Ok(())
 }
+
+/// Id of the variant, `None` if the media doesn't have it.
+pub async fn get_media_variant(
+    pool: &Pool<Postgres>,
+    media_id: &str,
+    name: &str,
+) -> Result<Option<String>, Error> {
+    let res = sqlx::query!(
+        "SELECT variant_id FROM media_variants WHERE media_id = $1 AND name = $2",
+        media_id,
+        name
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(res.map(|r| r.variant_id))
+}
+
+pub async fn get_media(pool: &Pool<Postgres>, media_id: &str) -> Result<Option<Media>, Error> {
+    let res = sqlx::query_as!(
+        Media,
+        "SELECT media_id, mime_type, size, width, height, blurhash, duration_ms FROM media WHERE media_id = $1",
+        media_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(res)
+}
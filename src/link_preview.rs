@@ -0,0 +1,543 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::LazyLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use regex::Regex;
+use reqwest::{
+    header::{ACCEPT, CONTENT_TYPE, LOCATION},
+    redirect::Policy,
+    Url,
+};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Orbit, Rocket,
+};
+use sqlx::{Pool, Postgres};
+use tokio::{net::lookup_host, task::JoinSet, time::timeout};
+
+use crate::database::{self, LinkPreview};
+
+const LINK_PREVIEWS_INTERVAL: Duration = Duration::from_secs(5);
+const LINK_PREVIEWS_BATCH_SIZE: i64 = 10;
+/// URLs left in `fetching` for longer than this are assumed to belong to a
+/// worker that died and are claimed again.
+const LINK_PREVIEW_STALE_MILLIS: i64 = 60 * 1000;
+const LINK_PREVIEW_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const LINK_PREVIEW_TIMEOUT: Duration = Duration::from_secs(5);
+const LINK_PREVIEW_MAX_BODY_BYTES: usize = 512 * 1024;
+const LINK_PREVIEW_MAX_REDIRECTS: usize = 3;
+const LINK_PREVIEW_TITLE_MAX_LEN: usize = 300;
+const LINK_PREVIEW_DESCRIPTION_MAX_LEN: usize = 1000;
+
+/// Only for local development, otherwise posting a link to an internal
+/// address would make the server fetch it.
+fn allow_private_ips() -> bool {
+    dotenv::var("LINK_PREVIEW_ALLOW_PRIVATE_IPS").is_ok_and(|v| v == "true")
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // 0.0.0.0/8, 100.64.0.0/10 (carrier-grade NAT) and 240.0.0.0/4
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64)
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ip));
+            }
+            let [first, second, ..] = ip.segments();
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // unique local fc00::/7 and link local fe80::/10
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                // NAT64 64:ff9b::/96 and 6to4 2002::/16 reach IPv4 addresses
+                // through a gateway, which could be private ones
+                || (first == 0x64 && second == 0xff9b)
+                || first == 0x2002)
+        }
+    }
+}
+
+/// Resolves the host of the URL, `None` if it can't be resolved or any of
+/// its addresses isn't allowed.
+async fn resolve(url: &Url, allow_private: bool) -> Option<Vec<SocketAddr>> {
+    let host = url.host_str()?;
+    let port = url.port_or_known_default()?;
+    let addrs: Vec<SocketAddr> = match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => timeout(LINK_PREVIEW_CONNECT_TIMEOUT, lookup_host((host, port)))
+            .await
+            .ok()?
+            .ok()?
+            .collect(),
+    };
+    if addrs.is_empty() || (!allow_private && addrs.iter().any(|a| !is_public_ip(a.ip()))) {
+        return None;
+    }
+    Some(addrs)
+}
+
+/// Fetches the page and parses its preview. Redirects are followed by hand so
+/// every hop is checked, and the client is pinned to the checked addresses so
+/// the host can't resolve somewhere else in between.
+async fn fetch_link_preview(url: &str, allow_private: bool) -> Option<LinkPreview> {
+    let mut current = Url::parse(url).ok()?;
+
+    for _ in 0..=LINK_PREVIEW_MAX_REDIRECTS {
+        if !matches!(current.scheme(), "http" | "https") {
+            return None;
+        }
+        let addrs = resolve(&current, allow_private).await?;
+        let client = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .connect_timeout(LINK_PREVIEW_CONNECT_TIMEOUT)
+            .timeout(LINK_PREVIEW_TIMEOUT)
+            .resolve_to_addrs(current.host_str()?, &addrs)
+            .user_agent("X-V-Server link preview")
+            .build()
+            .ok()?;
+        let mut res = client
+            .get(current.clone())
+            .header(ACCEPT, "text/html")
+            .send()
+            .await
+            .ok()?;
+
+        if res.status().is_redirection() {
+            let location = res.headers().get(LOCATION)?.to_str().ok()?;
+            current = current.join(location).ok()?;
+            continue;
+        }
+        if !res.status().is_success() {
+            return None;
+        }
+        let is_html = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|c| c.to_str().ok())
+            .is_some_and(|c| c.contains("text/html"));
+        if !is_html {
+            return None;
+        }
+
+        // the metadata lives in the head, anything past the limit is dropped
+        let mut body: Vec<u8> = vec![];
+        while let Ok(Some(chunk)) = res.chunk().await {
+            body.extend_from_slice(&chunk);
+            if body.len() >= LINK_PREVIEW_MAX_BODY_BYTES {
+                body.truncate(LINK_PREVIEW_MAX_BODY_BYTES);
+                break;
+            }
+        }
+
+        return parse_link_preview(url, &current, &String::from_utf8_lossy(&body));
+    }
+
+    None
+}
+
+fn decode_html_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn truncate(text: String, max_len: usize) -> String {
+    text.chars().take(max_len).collect()
+}
+
+/// Reads the OpenGraph and Twitter card metadata of the page, `None` if the
+/// page has nothing worth previewing.
+fn parse_link_preview(url: &str, page_url: &Url, html: &str) -> Option<LinkPreview> {
+    static META_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?is)<meta\s[^>]*>").unwrap());
+    static ATTR_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"(?s)([\w:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+    static TITLE_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+
+    let mut tags: HashMap<String, String> = HashMap::new();
+    for meta in META_REGEX.find_iter(html) {
+        let mut key: Option<String> = None;
+        let mut content: Option<&str> = None;
+        for c in ATTR_REGEX.captures_iter(meta.as_str()) {
+            let value = c.get(2).or(c.get(3)).map(|v| v.as_str());
+            match c[1].to_lowercase().as_str() {
+                "property" | "name" => key = value.map(|v| v.to_lowercase()),
+                "content" => content = value,
+                _ => {}
+            }
+        }
+        if let (Some(key), Some(content)) = (key, content) {
+            let content = decode_html_entities(content.trim());
+            if !content.is_empty() {
+                // the first occurrence wins, like browsers and crawlers do
+                tags.entry(key).or_insert(content);
+            }
+        }
+    }
+    let get = |keys: &[&str]| keys.iter().find_map(|k| tags.get(*k).cloned());
+
+    let title = get(&["og:title", "twitter:title"])
+        .or_else(|| {
+            TITLE_REGEX
+                .captures(html)
+                .map(|c| decode_html_entities(c[1].trim()))
+                .filter(|t| !t.is_empty())
+        })
+        .map(|t| truncate(t, LINK_PREVIEW_TITLE_MAX_LEN));
+    let description = get(&["og:description", "twitter:description", "description"])
+        .map(|d| truncate(d, LINK_PREVIEW_DESCRIPTION_MAX_LEN));
+    let image = get(&[
+        "og:image",
+        "og:image:url",
+        "twitter:image",
+        "twitter:image:src",
+    ])
+    .and_then(|i| page_url.join(&i).ok())
+    .filter(|i| matches!(i.scheme(), "http" | "https"))
+    .map(String::from);
+    let site_name = get(&["og:site_name"]).map(|s| truncate(s, LINK_PREVIEW_TITLE_MAX_LEN));
+
+    if title.is_none() && description.is_none() && image.is_none() {
+        return None;
+    }
+
+    Some(LinkPreview {
+        url: url.to_string(),
+        title,
+        description,
+        image,
+        site_name,
+    })
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64
+}
+
+async fn fetch_link_previews(pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+    let now = now_millis();
+    let urls = database::claim_link_previews(
+        &now,
+        &(now - LINK_PREVIEW_STALE_MILLIS),
+        &LINK_PREVIEWS_BATCH_SIZE,
+        pool,
+    )
+    .await?;
+
+    let allow_private = allow_private_ips();
+    let mut fetches = JoinSet::new();
+    for url in urls {
+        fetches.spawn(async move {
+            let preview = fetch_link_preview(&url, allow_private).await;
+            (url, preview)
+        });
+    }
+    while let Some(res) = fetches.join_next().await {
+        let Ok((url, preview)) = res else {
+            continue;
+        };
+        database::save_link_preview(&url, &preview, &now_millis(), pool).await?;
+    }
+    Ok(())
+}
+
+/// Spawns the background worker that fetches the previews of the links
+/// queued when posts are published or edited.
+pub struct LinkPreviews;
+
+#[rocket::async_trait]
+impl Fairing for LinkPreviews {
+    fn info(&self) -> Info {
+        Info {
+            name: "Fetch link previews",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, _rocket: &Rocket<Orbit>) {
+        tokio::spawn(async {
+            let pool = database::connect_db().await;
+            let mut interval = tokio::time::interval(LINK_PREVIEWS_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = fetch_link_previews(&pool).await {
+                    error!("unable to fetch link previews: {e}");
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        time::sleep,
+    };
+
+    use super::*;
+
+    /// What the test server answers to a request.
+    enum Reply {
+        Html(String),
+        Redirect(String),
+        /// Sends the headers and the start of the page, then hangs.
+        Stall(String),
+    }
+
+    /// Serves the replies of `handler` on a local port, returns its address.
+    async fn serve(handler: fn(&str) -> Reply) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+                tokio::spawn(async move {
+                    let mut request = vec![];
+                    let mut buf = [0; 1024];
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request);
+                    let path = request.split(' ').nth(1).unwrap_or("/");
+                    let (head, body, stall) = match handler(path) {
+                        Reply::Html(body) => (
+                            format!(
+                                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                                body.len()
+                            ),
+                            body,
+                            false,
+                        ),
+                        Reply::Redirect(location) => (
+                            format!(
+                                "HTTP/1.1 302 Found\r\nLocation: {location}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            ),
+                            String::new(),
+                            false,
+                        ),
+                        Reply::Stall(body) => (
+                            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 100000\r\nConnection: close\r\n\r\n"
+                                .to_string(),
+                            body,
+                            true,
+                        ),
+                    };
+                    let _ = stream.write_all(head.as_bytes()).await;
+                    let _ = stream.write_all(body.as_bytes()).await;
+                    let _ = stream.flush().await;
+                    if stall {
+                        sleep(Duration::from_secs(60)).await;
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn parses_open_graph_tags() {
+        let addr = serve(|_| {
+            Reply::Html(
+                r#"<html><head>
+                <title>Fallback</title>
+                <meta property="og:title" content="Tom &amp; Jerry">
+                <meta content='A cat and a mouse' property='og:description'>
+                <meta property="og:image" content="/cover.png">
+                <meta property="og:site_name" content="Cartoons">
+                <meta property="og:title" content="Ignored">
+                </head></html>"#
+                    .to_string(),
+            )
+        })
+        .await;
+        let url = format!("http://{addr}/show");
+
+        let preview = fetch_link_preview(&url, true).await.unwrap();
+        assert_eq!(preview.url, url);
+        assert_eq!(preview.title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(preview.description.as_deref(), Some("A cat and a mouse"));
+        assert_eq!(preview.image, Some(format!("http://{addr}/cover.png")));
+        assert_eq!(preview.site_name.as_deref(), Some("Cartoons"));
+    }
+
+    #[tokio::test]
+    async fn parses_twitter_cards() {
+        let addr = serve(|_| {
+            Reply::Html(
+                r#"<head>
+                <META NAME="twitter:title" CONTENT="Card title">
+                <meta name="twitter:description" content="Card description">
+                <meta name="twitter:image:src" content="https://cdn.example.com/card.jpg">
+                <meta name="twitter:image:alt" content="">
+                </head>"#
+                    .to_string(),
+            )
+        })
+        .await;
+
+        let preview = fetch_link_preview(&format!("http://{addr}/"), true)
+            .await
+            .unwrap();
+        assert_eq!(preview.title.as_deref(), Some("Card title"));
+        assert_eq!(preview.description.as_deref(), Some("Card description"));
+        assert_eq!(
+            preview.image.as_deref(),
+            Some("https://cdn.example.com/card.jpg")
+        );
+        assert_eq!(preview.site_name, None);
+    }
+
+    #[tokio::test]
+    async fn skips_pages_without_metadata() {
+        let addr = serve(|_| Reply::Html("<p>nothing to see</p>".to_string())).await;
+
+        assert!(fetch_link_preview(&format!("http://{addr}/"), true)
+            .await
+            .is_none());
+    }
+
+    /// `/hops/N` redirects N more times before the page.
+    fn redirects(path: &str) -> Reply {
+        match path.trim_start_matches("/hops/").parse::<usize>() {
+            Ok(0) | Err(_) => {
+                Reply::Html(r#"<meta property="og:title" content="Landed">"#.to_string())
+            }
+            Ok(n) => Reply::Redirect(format!("/hops/{}", n - 1)),
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_redirects() {
+        let addr = serve(redirects).await;
+        let url = format!("http://{addr}/hops/{LINK_PREVIEW_MAX_REDIRECTS}");
+
+        let preview = fetch_link_preview(&url, true).await.unwrap();
+        // the preview is kept under the URL that was posted
+        assert_eq!(preview.url, url);
+        assert_eq!(preview.title.as_deref(), Some("Landed"));
+    }
+
+    #[tokio::test]
+    async fn limits_redirects() {
+        let addr = serve(redirects).await;
+        let url = format!("http://{addr}/hops/{}", LINK_PREVIEW_MAX_REDIRECTS + 1);
+
+        assert!(fetch_link_preview(&url, true).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn caps_the_body_size() {
+        let addr = serve(|_| {
+            Reply::Html(format!(
+                r#"<meta property="og:title" content="Big page">{}<meta property="og:description" content="Too far">"#,
+                " ".repeat(LINK_PREVIEW_MAX_BODY_BYTES)
+            ))
+        })
+        .await;
+
+        let preview = fetch_link_preview(&format!("http://{addr}/"), true)
+            .await
+            .unwrap();
+        assert_eq!(preview.title.as_deref(), Some("Big page"));
+        assert_eq!(preview.description, None);
+    }
+
+    #[tokio::test]
+    async fn caps_the_fetch_time() {
+        let addr = serve(|_| {
+            Reply::Stall(r#"<meta property="og:title" content="Slow page">"#.to_string())
+        })
+        .await;
+
+        let start = Instant::now();
+        let preview = fetch_link_preview(&format!("http://{addr}/"), true).await;
+        assert!(start.elapsed() < LINK_PREVIEW_TIMEOUT + Duration::from_secs(2));
+        // what arrived before the timeout is still used
+        assert_eq!(preview.unwrap().title.as_deref(), Some("Slow page"));
+    }
+
+    #[tokio::test]
+    async fn rejects_private_addresses() {
+        let addr =
+            serve(|_| Reply::Html(r#"<meta property="og:title" content="Internal">"#.to_string()))
+                .await;
+
+        assert!(fetch_link_preview(&format!("http://{addr}/"), false)
+            .await
+            .is_none());
+        assert!(
+            fetch_link_preview(&format!("http://localhost:{}/", addr.port()), false)
+                .await
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn classifies_ips() {
+        let public = [
+            "93.184.216.34",
+            "2606:2800:220:1::1",
+            "::ffff:93.184.216.34",
+        ];
+        let private = [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "240.0.0.1",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a00:1",
+            "2002:a00:1::1",
+        ];
+        for ip in public {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip} is public");
+        }
+        for ip in private {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip} is private");
+        }
+    }
+}
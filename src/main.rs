@@ -1,7 +1,12 @@
 mod auth;
 mod cors;
 mod database;
+mod link_preview;
+mod media;
 mod routes;
+mod scheduler;
+mod suggestions;
+mod trending;
 
 use core::str;
 
@@ -35,6 +40,7 @@ pub const USER_AT_MAX_LEN: usize = 20;
 pub const PASSWORD_MIN_LEN: usize = 8;
 pub const PASSWORD_MAX_LEN: usize = 32;
 pub const BIO_MAX_LEN: usize = 150;
+pub const POST_EDIT_WINDOW_DEFAULT_MINUTES: i64 = 60;
 
 #[macro_use]
 extern crate rocket;
@@ -191,39 +197,89 @@ async fn rocket() -> _ {
     let config = Config::figment()
         .merge(("port", 10_000))
         .merge(("address", "0.0.0.0"));
-    rocket::custom(config).attach(cors::CORS).mount(
-        "/",
-        routes![
-            get_slash,
-            routes::user::create,
-            routes::user::login,
-            routes::user::logout,
-            routes::user::delete,
-            routes::auth::validate,
-            options,
-            routes::user_get::get_data,
-            routes::user_get::get_profile_data,
-            routes::user_get::get_following,
-            routes::user_get::get_followers,
-            routes::user_get::query,
-            routes::user_get::fetch_comments,
-            routes::change::change_profile,
-            routes::change::change_password,
-            routes::change::change_email,
-            routes::change::change_user_at,
-            routes::change::follow_user,
-            routes::change::edit_post,
-            routes::user::publish_post,
-            routes::user::fetch_posts,
-            routes::user::fetch_post,
-            routes::user::fetch_user_posts,
-            routes::user::like,
-            routes::user::like_comment,
-            routes::user::comment,
-            routes::user::delete_post,
-            routes::user::delete_comment,
-        ],
-    )
+    rocket::custom(config)
+        .attach(cors::CORS)
+        .attach(media::legacy::LegacyMedia)
+        .attach(scheduler::ScheduledPosts)
+        .attach(link_preview::LinkPreviews)
+        .manage(trending::TrendingCache::default())
+        .attach(trending::TrendingWorker)
+        .manage(suggestions::SuggestionsCache::default())
+        .mount(
+            "/",
+            routes![
+                get_slash,
+                routes::user::create,
+                routes::user::login,
+                routes::user::logout,
+                routes::user::delete,
+                routes::auth::validate,
+                options,
+                routes::user_get::get_data,
+                routes::user_get::get_profile_data,
+                routes::user_get::get_following,
+                routes::user_get::get_followers,
+                routes::user_get::query,
+                routes::user_get::search_posts,
+                routes::user_get::fetch_comments,
+                routes::user_get::fetch_post_history,
+                routes::user_get::fetch_post_likes,
+                routes::user_get::fetch_comment_likes,
+                routes::change::change_profile,
+                routes::change::change_password,
+                routes::change::change_email,
+                routes::change::change_user_at,
+                routes::change::follow_user,
+                routes::change::change_private,
+                routes::change::edit_post,
+                routes::change::change_sensitive_media,
+                routes::user::publish_post,
+                routes::user::fetch_posts,
+                routes::user::fetch_post,
+                routes::user::fetch_user_posts,
+                routes::user::like,
+                routes::user::unlike,
+                routes::user::like_comment,
+                routes::user::unlike_comment,
+                routes::user::vote_poll,
+                routes::user::comment,
+                routes::user::delete_post,
+                routes::user::delete_comment,
+                routes::user::bookmark,
+                routes::user::remove_bookmark,
+                routes::user::fetch_bookmarks,
+                routes::user::pin_post,
+                routes::user::unpin_post,
+                routes::blocks::block_user,
+                routes::blocks::unblock_user,
+                routes::blocks::fetch_blocks,
+                routes::mutes::mute_user,
+                routes::mutes::unmute_user,
+                routes::mutes::fetch_mutes,
+                routes::mutes::add_muted_word,
+                routes::mutes::fetch_muted_words,
+                routes::mutes::delete_muted_word,
+                routes::follow_requests::fetch_incoming_follow_requests,
+                routes::follow_requests::fetch_outgoing_follow_requests,
+                routes::follow_requests::approve_follow_request,
+                routes::follow_requests::reject_follow_request,
+                routes::suggestions::fetch_suggestions,
+                routes::suggestions::dismiss_suggestion,
+                routes::scheduled::fetch_scheduled_posts,
+                routes::scheduled::edit_scheduled_post,
+                routes::scheduled::cancel_scheduled_post,
+                routes::drafts::create_draft,
+                routes::drafts::edit_draft,
+                routes::drafts::fetch_drafts,
+                routes::drafts::delete_draft,
+                routes::drafts::publish_draft,
+                routes::trending::trending_posts,
+                routes::trending::trending_tags,
+                routes::media::get_media,
+                routes::media::get_media_variant,
+                routes::media::upload_media,
+            ],
+        )
 }
 
 #[options("/<_..>")]
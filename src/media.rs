@@ -0,0 +1,450 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufReader, Cursor},
+    path::Path,
+    sync::{Arc, OnceLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rocket::{fs::TempFile, http::Status, response::status::Custom, tokio::io::AsyncReadExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Postgres};
+
+use crate::database::{self, Media};
+
+use processing::{EncodedImage, ProcessedImage};
+
+pub mod legacy;
+pub mod local;
+pub mod processing;
+pub mod s3;
+pub mod video;
+
+#[derive(Debug)]
+pub enum MediaError {
+    /// The reference or data sent by the client can't be used.
+    Invalid,
+    /// The reference points to media that doesn't exist.
+    NotFound,
+    /// The content isn't one of the [`MEDIA_KINDS`].
+    Unsupported,
+    /// The content is bigger than its kind allows.
+    TooLarge,
+    /// The GIF or video is longer than [`MEDIA_MAX_DURATION`].
+    TooLong,
+    Storage(String),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for MediaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaError::Invalid => write!(f, "invalid media"),
+            MediaError::NotFound => write!(f, "media not found"),
+            MediaError::Unsupported => write!(f, "unsupported media type"),
+            MediaError::TooLarge => write!(f, "media too large"),
+            MediaError::TooLong => write!(f, "media too long"),
+            MediaError::Storage(e) => write!(f, "media storage error: {e}"),
+            MediaError::Database(e) => write!(f, "media database error: {e}"),
+        }
+    }
+}
+
+impl From<sqlx::Error> for MediaError {
+    fn from(e: sqlx::Error) -> Self {
+        MediaError::Database(e)
+    }
+}
+
+impl From<MediaError> for Custom<&'static str> {
+    fn from(e: MediaError) -> Self {
+        match e {
+            MediaError::Invalid => Custom(Status::BadRequest, "Invalid media"),
+            MediaError::NotFound => Custom(Status::BadRequest, "Media not found"),
+            MediaError::Unsupported => {
+                Custom(Status::UnsupportedMediaType, "Unsupported media type")
+            }
+            MediaError::TooLarge => Custom(Status::PayloadTooLarge, "Media too large"),
+            MediaError::TooLong => Custom(Status::BadRequest, "Media too long"),
+            MediaError::Storage(..) | MediaError::Database(..) => {
+                error!("{e}");
+                Custom(Status::InternalServerError, "InternalServerError")
+            }
+        }
+    }
+}
+
+/// Where the bytes of the media live, the metadata is kept in the `media`
+/// table. Ids are content addressed, so writing the same id twice always
+/// writes the same bytes.
+#[rocket::async_trait]
+pub trait MediaStore: Send + Sync {
+    async fn put(&self, id: &str, data: &[u8]) -> Result<(), MediaError>;
+    /// `None` if nothing is stored under the id.
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>, MediaError>;
+
+    /// Stores the content of a file without loading it into memory, uploads
+    /// and videos can be far bigger than what should be buffered.
+    async fn put_file(&self, id: &str, path: &Path) -> Result<(), MediaError>;
+
+    /// Bytes `start` to `end` inclusive, used to serve range requests.
+    async fn get_range(
+        &self,
+        id: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Option<Vec<u8>>, MediaError> {
+        Ok(self.get(id).await?.map(|data| {
+            let end = (end as usize + 1).min(data.len());
+            data.get(start as usize..end).unwrap_or_default().to_vec()
+        }))
+    }
+}
+
+/// How clients should show the media, derived from its mime type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    Image,
+    Gif,
+    Video,
+}
+
+impl MediaType {
+    pub fn from_mime_type(mime_type: &str) -> Self {
+        match mime_type {
+            "image/gif" => MediaType::Gif,
+            m if m.starts_with("video/") => MediaType::Video,
+            _ => MediaType::Image,
+        }
+    }
+}
+
+pub struct MediaKind {
+    pub mime_type: &'static str,
+    pub max_bytes: u64,
+    sniff: fn(&[u8]) -> bool,
+}
+
+const MIB: u64 = 1024 * 1024;
+
+/// Longest GIF or video accepted.
+pub const MEDIA_MAX_DURATION: Duration = Duration::from_secs(140);
+
+/// MP4 brands of plain video files, other ISO BMFF files such as HEIC images
+/// or QuickTime movies share the `ftyp` box.
+const MP4_BRANDS: [&[u8]; 9] = [
+    b"isom", b"iso2", b"iso4", b"iso5", b"iso6", b"mp41", b"mp42", b"avc1", b"dash",
+];
+
+/// What can be uploaded, recognized by the magic bytes at the start of the
+/// content rather than what the client claims it is.
+pub const MEDIA_KINDS: [MediaKind; 6] = [
+    MediaKind {
+        mime_type: "image/png",
+        max_bytes: 8 * MIB,
+        sniff: |head| head.starts_with(b"\x89PNG\r\n\x1a\n"),
+    },
+    MediaKind {
+        mime_type: "image/jpeg",
+        max_bytes: 8 * MIB,
+        sniff: |head| head.starts_with(b"\xff\xd8\xff"),
+    },
+    MediaKind {
+        mime_type: "image/gif",
+        max_bytes: 15 * MIB,
+        sniff: |head| head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a"),
+    },
+    MediaKind {
+        mime_type: "image/webp",
+        max_bytes: 8 * MIB,
+        sniff: |head| head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP"),
+    },
+    MediaKind {
+        mime_type: "video/mp4",
+        max_bytes: 40 * MIB,
+        sniff: |head| {
+            head.get(4..8) == Some(b"ftyp")
+                && head
+                    .get(8..12)
+                    .is_some_and(|brand| MP4_BRANDS.contains(&brand))
+        },
+    },
+    MediaKind {
+        mime_type: "video/webm",
+        // the EBML header, the doc type is checked when the container is read
+        max_bytes: 40 * MIB,
+        sniff: |head| head.starts_with(b"\x1a\x45\xdf\xa3"),
+    },
+];
+
+/// Bytes needed by [`sniff_media_kind`].
+const MEDIA_SNIFF_LEN: usize = 12;
+
+pub fn sniff_media_kind(head: &[u8]) -> Option<&'static MediaKind> {
+    MEDIA_KINDS.iter().find(|kind| (kind.sniff)(head))
+}
+
+static MEDIA_STORE: OnceLock<Arc<dyn MediaStore>> = OnceLock::new();
+
+/// The store configured through `MEDIA_STORE`, `local` (the default) or `s3`.
+pub fn media_store() -> Arc<dyn MediaStore> {
+    MEDIA_STORE
+        .get_or_init(|| match dotenv::var("MEDIA_STORE").as_deref() {
+            Ok("s3") => Arc::new(s3::S3MediaStore::from_env()),
+            _ => Arc::new(local::LocalMediaStore::from_env()),
+        })
+        .clone()
+}
+
+/// Hex encoded SHA-256 of the content.
+pub fn media_id(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+pub fn is_media_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Public URL of the media, `MEDIA_PUBLIC_URL` is prepended so clients on
+/// another origin can load it.
+pub fn media_url(media_id: Option<String>) -> String {
+    let Some(id) = media_id else {
+        return String::new();
+    };
+    let base = dotenv::var("MEDIA_PUBLIC_URL").unwrap_or_default();
+    format!("{}/media/{id}", base.trim_end_matches('/'))
+}
+
+/// Splits a `data:<mime>;base64,<data>` URL.
+pub fn decode_data_url(data_url: &str) -> Option<(String, Vec<u8>)> {
+    let (header, data) = data_url.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = header.strip_suffix(";base64")?;
+    if mime_type.is_empty() {
+        return None;
+    }
+    let data = STANDARD.decode(data).ok()?;
+    Some((mime_type.to_lowercase(), data))
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64
+}
+
+/// Image processing is CPU bound, so it's kept off the async workers.
+async fn run_blocking<F, T>(f: F) -> Result<T, MediaError>
+where
+    F: FnOnce() -> Result<T, MediaError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| MediaError::Storage(e.to_string()))?
+}
+
+/// Stores the content once and returns its id, storing content that already
+/// exists only returns the existing id.
+async fn save_encoded(
+    image: &EncodedImage,
+    blurhash: Option<String>,
+    duration: Option<Duration>,
+    pool: &Pool<Postgres>,
+) -> Result<String, MediaError> {
+    let id = media_id(&image.data);
+    if database::media_exists(pool, &id).await? {
+        return Ok(id);
+    }
+    media_store().put(&id, &image.data).await?;
+
+    let media = Media {
+        media_id: id.clone(),
+        mime_type: image.mime_type.to_string(),
+        size: image.data.len() as i64,
+        width: Some(image.width as i32),
+        height: Some(image.height as i32),
+        blurhash,
+        duration_ms: duration.map(|d| d.as_millis() as i32),
+    };
+    database::insert_media(pool, &media, &now_millis()).await?;
+    Ok(id)
+}
+
+/// Stores the image and its variants. Returns the id and the mime type of
+/// the image.
+async fn save_image(
+    image: ProcessedImage,
+    pool: &Pool<Postgres>,
+) -> Result<(String, &'static str), MediaError> {
+    let id = save_encoded(&image.encoded, Some(image.blurhash), image.duration, pool).await?;
+    save_variants(&id, &image.variants, pool).await?;
+    Ok((id, image.encoded.mime_type))
+}
+
+async fn save_variants(
+    media_id: &str,
+    variants: &[(&'static str, EncodedImage)],
+    pool: &Pool<Postgres>,
+) -> Result<(), MediaError> {
+    for (name, variant) in variants {
+        let variant_id = save_encoded(variant, None, None, pool).await?;
+        database::insert_media_variant(pool, media_id, name, &variant_id).await?;
+    }
+    Ok(())
+}
+
+/// Stores the video as it is, with its first frame as the `poster` variant
+/// and the smaller variants made from it. Videos without a poster are still
+/// stored, ffmpeg may be missing or unable to decode the stream.
+async fn save_video(
+    path: &Path,
+    id: String,
+    size: u64,
+    kind: &'static MediaKind,
+    pool: &Pool<Postgres>,
+) -> Result<(String, &'static str), MediaError> {
+    let info = run_blocking({
+        let path = path.to_path_buf();
+        move || {
+            let file = File::open(path).map_err(|e| MediaError::Storage(e.to_string()))?;
+            video::probe_video(BufReader::new(file), kind.mime_type)
+        }
+    })
+    .await?;
+
+    let poster = match video::extract_poster(path).await {
+        Ok(png) => run_blocking(move || processing::process_image(Cursor::new(png))).await,
+        Err(e) => Err(e),
+    };
+    let poster = match poster {
+        Ok(poster) => Some(poster),
+        Err(e) => {
+            error!("unable to make the poster of {id}: {e}");
+            None
+        }
+    };
+
+    if !database::media_exists(pool, &id).await? {
+        media_store().put_file(&id, path).await?;
+        let media = Media {
+            media_id: id.clone(),
+            mime_type: kind.mime_type.to_string(),
+            size: size as i64,
+            width: Some(info.width as i32),
+            height: Some(info.height as i32),
+            blurhash: poster.as_ref().map(|p| p.blurhash.clone()),
+            duration_ms: Some(info.duration.as_millis() as i32),
+        };
+        database::insert_media(pool, &media, &now_millis()).await?;
+    }
+
+    if let Some(poster) = poster {
+        let poster_id =
+            save_encoded(&poster.encoded, Some(poster.blurhash.clone()), None, pool).await?;
+        database::insert_media_variant(pool, &id, "poster", &poster_id).await?;
+        save_variants(&id, &poster.variants, pool).await?;
+    }
+    Ok((id, kind.mime_type))
+}
+
+/// Stores an uploaded file, its kind is sniffed from the content. Returns the
+/// id and the mime type.
+pub async fn save_upload(
+    file: &TempFile<'_>,
+    pool: &Pool<Postgres>,
+) -> Result<(String, &'static str), MediaError> {
+    // text fields end up buffered in memory, files are always on disk
+    let path = file.path().ok_or(MediaError::Invalid)?;
+    let mut reader = file
+        .open()
+        .await
+        .map_err(|e| MediaError::Storage(e.to_string()))?;
+
+    let mut hasher = Sha256::new();
+    let mut head: Vec<u8> = vec![];
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .await
+            .map_err(|e| MediaError::Storage(e.to_string()))?;
+        if n == 0 {
+            break;
+        }
+        if head.len() < MEDIA_SNIFF_LEN {
+            let missing = (MEDIA_SNIFF_LEN - head.len()).min(n);
+            head.extend_from_slice(&buf[..missing]);
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    let kind = sniff_media_kind(&head).ok_or(MediaError::Unsupported)?;
+    if file.len() > kind.max_bytes {
+        return Err(MediaError::TooLarge);
+    }
+
+    let id = hex::encode(hasher.finalize());
+    if MediaType::from_mime_type(kind.mime_type) == MediaType::Video {
+        return save_video(path, id, file.len(), kind, pool).await;
+    }
+
+    let image = run_blocking({
+        let path = path.to_path_buf();
+        move || {
+            let file = File::open(path).map_err(|e| MediaError::Storage(e.to_string()))?;
+            processing::process_image(BufReader::new(file))
+        }
+    })
+    .await?;
+    save_image(image, pool).await
+}
+
+/// Turns the media into an icon, cropped square in fixed sizes. Returns the id
+/// of the icon, which is the same media for icons that were already made.
+pub async fn make_icon(media_id: &str, pool: &Pool<Postgres>) -> Result<String, MediaError> {
+    let data = media_store()
+        .get(media_id)
+        .await?
+        .ok_or(MediaError::NotFound)?;
+    let icon = run_blocking(move || processing::process_icon(Cursor::new(data))).await?;
+    let (id, _) = save_image(icon, pool).await?;
+    Ok(id)
+}
+
+/// Turns what a client sent as media into a media id. Accepts base64 data
+/// URLs, which are stored, and ids or URLs of media that already exists, so
+/// clients can send back what they received.
+pub async fn resolve_media(value: &str, pool: &Pool<Postgres>) -> Result<String, MediaError> {
+    if value.starts_with("data:") {
+        // what the data URL claims is ignored, like for uploads
+        let (_, data) = decode_data_url(value).ok_or(MediaError::Invalid)?;
+        let kind = sniff_media_kind(&data).ok_or(MediaError::Unsupported)?;
+        // videos need a file for ffmpeg, they can only be uploaded
+        if MediaType::from_mime_type(kind.mime_type) == MediaType::Video {
+            return Err(MediaError::Unsupported);
+        }
+        if data.len() as u64 > kind.max_bytes {
+            return Err(MediaError::TooLarge);
+        }
+        let image = run_blocking(move || processing::process_image(Cursor::new(data))).await?;
+        let (id, _) = save_image(image, pool).await?;
+        return Ok(id);
+    }
+
+    let id = match value.rsplit_once("/media/") {
+        Some((_, id)) => id,
+        None => value,
+    };
+    if !is_media_id(id) {
+        return Err(MediaError::Invalid);
+    }
+    if !database::media_exists(pool, id).await? {
+        return Err(MediaError::NotFound);
+    }
+    Ok(id.to_string())
+}
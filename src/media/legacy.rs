@@ -0,0 +1,227 @@
+//! Images used to be kept in the rows themselves, as the bytes of the data URL
+//! the client sent. Databases migrated with `0012_media.sql` still have those
+//! columns, their content is moved to the media store when the server starts
+//! and each column is dropped once all its rows are moved.
+
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    Build, Rocket,
+};
+use sqlx::{Error, Pool, Postgres, Row};
+
+use crate::database;
+
+use super::{make_icon, resolve_media, MediaError};
+
+/// A column that used to hold the media and the one holding its id now.
+struct LegacyColumn {
+    table: &'static str,
+    /// Integer columns identifying a row.
+    key: &'static [&'static str],
+    column: &'static str,
+    id_column: &'static str,
+    /// Icons are cropped square like the ones set through `/change/profile`.
+    icon: bool,
+    /// Whether every row must have media, `id_column` is set NOT NULL once
+    /// the column is dropped.
+    required: bool,
+}
+
+const LEGACY_COLUMNS: [LegacyColumn; 6] = [
+    LegacyColumn {
+        table: "users",
+        key: &["id"],
+        column: "icon",
+        id_column: "icon_id",
+        icon: true,
+        required: false,
+    },
+    LegacyColumn {
+        table: "posts",
+        key: &["post_id"],
+        column: "image",
+        id_column: "image_id",
+        icon: false,
+        required: false,
+    },
+    LegacyColumn {
+        table: "comments",
+        key: &["post_id"],
+        column: "image",
+        id_column: "image_id",
+        icon: false,
+        required: false,
+    },
+    LegacyColumn {
+        table: "post_revisions",
+        key: &["revision_id"],
+        column: "image",
+        id_column: "image_id",
+        icon: false,
+        required: false,
+    },
+    LegacyColumn {
+        table: "post_attachments",
+        key: &["post_id", "position"],
+        column: "data",
+        id_column: "media_id",
+        icon: false,
+        required: true,
+    },
+    LegacyColumn {
+        table: "comment_attachments",
+        key: &["comment_id", "position"],
+        column: "data",
+        id_column: "media_id",
+        icon: false,
+        required: true,
+    },
+];
+
+/// Stores what was in the column, `None` for the empty values kept for rows
+/// without media.
+async fn move_media(
+    data: &[u8],
+    column: &LegacyColumn,
+    pool: &Pool<Postgres>,
+) -> Result<Option<String>, MediaError> {
+    let value = std::str::from_utf8(data)
+        .map_err(|_| MediaError::Invalid)?
+        .trim();
+    if value.is_empty() {
+        return match column.required {
+            true => Err(MediaError::Invalid),
+            false => Ok(None),
+        };
+    }
+    let id = resolve_media(value, pool).await?;
+    if column.icon {
+        return make_icon(&id, pool).await.map(Some);
+    }
+    Ok(Some(id))
+}
+
+async fn column_exists(table: &str, column: &str, pool: &Pool<Postgres>) -> Result<bool, Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM information_schema.columns WHERE table_schema = 'public' AND table_name = $1 AND column_name = $2)",
+    )
+    .bind(table)
+    .bind(column)
+    .fetch_one(pool)
+    .await
+}
+
+/// Moves the rows one at a time, a row is moved once its legacy value is
+/// cleared. Rows that can't be moved are logged and keep the column around.
+async fn move_column(c: &LegacyColumn, pool: &Pool<Postgres>) -> Result<(), Error> {
+    let LegacyColumn {
+        table,
+        column,
+        id_column,
+        ..
+    } = c;
+    if !column_exists(table, column, pool).await? {
+        return Ok(());
+    }
+
+    let key = c
+        .key
+        .iter()
+        .map(|k| format!("\"{k}\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+    // the key is bound from `$first` on
+    let filter = |first: usize| {
+        c.key
+            .iter()
+            .enumerate()
+            .map(|(i, k)| format!("\"{k}\" = ${}", i + first))
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    };
+    let select = format!("SELECT {column} FROM {table} WHERE {}", filter(1));
+    let update = format!(
+        "UPDATE {table} SET {id_column} = $1, {column} = NULL WHERE {}",
+        filter(2)
+    );
+
+    let rows = sqlx::query(&format!(
+        "SELECT {key} FROM {table} WHERE {column} IS NOT NULL ORDER BY {key}"
+    ))
+    .fetch_all(pool)
+    .await?;
+    let mut failed = 0;
+    for row in rows {
+        let key = (0..c.key.len())
+            .map(|i| row.try_get::<i32, _>(i))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut query = sqlx::query_scalar::<_, Option<Vec<u8>>>(&select);
+        for k in &key {
+            query = query.bind(k);
+        }
+        let Some(data) = query.fetch_optional(pool).await?.flatten() else {
+            continue;
+        };
+
+        match move_media(&data, c, pool).await {
+            Ok(media_id) => {
+                let mut query = sqlx::query(&update).bind(media_id);
+                for k in &key {
+                    query = query.bind(k);
+                }
+                query.execute(pool).await?;
+            }
+            Err(e) => {
+                error!("unable to move the {column} of {table} {key:?} to the media store: {e}");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        error!("{table}.{column} is kept until its {failed} remaining rows are moved");
+        return Ok(());
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(&format!("ALTER TABLE {table} DROP COLUMN {column}"))
+        .execute(&mut *tx)
+        .await?;
+    if c.required {
+        sqlx::query(&format!(
+            "ALTER TABLE {table} ALTER COLUMN {id_column} SET NOT NULL"
+        ))
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    info!("moved {table}.{column} to the media store");
+    Ok(())
+}
+
+/// Moves the legacy media before the server starts handling requests, so
+/// nothing reads or writes the rows while they are moved.
+pub struct LegacyMedia;
+
+#[rocket::async_trait]
+impl Fairing for LegacyMedia {
+    fn info(&self) -> Info {
+        Info {
+            name: "Move legacy media to the media store",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let pool = database::connect_db().await;
+        for column in &LEGACY_COLUMNS {
+            if let Err(e) = move_column(column, &pool).await {
+                error!(
+                    "unable to move {}.{} to the media store: {e}",
+                    column.table, column.column
+                );
+            }
+        }
+        Ok(rocket)
+    }
+}
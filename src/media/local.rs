@@ -0,0 +1,94 @@
+use std::{
+    io::{ErrorKind, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use super::{MediaError, MediaStore};
+
+const MEDIA_LOCAL_DIR_DEFAULT: &str = "media";
+
+/// Stores media as files under `MEDIA_LOCAL_DIR`, sharded by the first two
+/// characters of the id.
+pub struct LocalMediaStore {
+    dir: PathBuf,
+}
+
+impl LocalMediaStore {
+    pub fn from_env() -> Self {
+        let dir = dotenv::var("MEDIA_LOCAL_DIR").unwrap_or(MEDIA_LOCAL_DIR_DEFAULT.to_string());
+        LocalMediaStore { dir: dir.into() }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(&id[..2]).join(id)
+    }
+}
+
+#[rocket::async_trait]
+impl MediaStore for LocalMediaStore {
+    async fn put(&self, id: &str, data: &[u8]) -> Result<(), MediaError> {
+        let path = self.path(id);
+        let parent = path.parent().expect("media path always has a parent");
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| MediaError::Storage(e.to_string()))?;
+
+        // written next to the final path and renamed so readers never see a
+        // partial file
+        let tmp = path.with_extension(format!("tmp-{}", rand::random::<u32>()));
+        tokio::fs::write(&tmp, data)
+            .await
+            .map_err(|e| MediaError::Storage(e.to_string()))?;
+        tokio::fs::rename(&tmp, &path)
+            .await
+            .map_err(|e| MediaError::Storage(e.to_string()))
+    }
+
+    async fn put_file(&self, id: &str, path: &Path) -> Result<(), MediaError> {
+        let dest = self.path(id);
+        let parent = dest.parent().expect("media path always has a parent");
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| MediaError::Storage(e.to_string()))?;
+
+        let tmp = dest.with_extension(format!("tmp-{}", rand::random::<u32>()));
+        tokio::fs::copy(path, &tmp)
+            .await
+            .map_err(|e| MediaError::Storage(e.to_string()))?;
+        tokio::fs::rename(&tmp, &dest)
+            .await
+            .map_err(|e| MediaError::Storage(e.to_string()))
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>, MediaError> {
+        match tokio::fs::read(self.path(id)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(MediaError::Storage(e.to_string())),
+        }
+    }
+
+    async fn get_range(
+        &self,
+        id: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Option<Vec<u8>>, MediaError> {
+        let mut file = match tokio::fs::File::open(self.path(id)).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(MediaError::Storage(e.to_string())),
+        };
+        file.seek(SeekFrom::Start(start))
+            .await
+            .map_err(|e| MediaError::Storage(e.to_string()))?;
+        let mut data = vec![];
+        file.take(end - start + 1)
+            .read_to_end(&mut data)
+            .await
+            .map_err(|e| MediaError::Storage(e.to_string()))?;
+        Ok(Some(data))
+    }
+}
@@ -0,0 +1,376 @@
+use std::{
+    io::{BufRead, Cursor, Seek},
+    time::Duration,
+};
+
+use image::{
+    codecs::{gif::GifDecoder, jpeg::JpegEncoder},
+    error::ImageError,
+    imageops::FilterType,
+    metadata::Orientation,
+    AnimationDecoder, DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits,
+};
+
+use super::{MediaError, MEDIA_MAX_DURATION};
+
+/// Longest side of stored images, bigger ones are scaled down.
+const IMAGE_MAX_DIMENSION: u32 = 4096;
+/// Checked against the header before decoding, so images that are tiny on
+/// the wire but huge once decoded never get allocated.
+const IMAGE_MAX_PIXELS: u64 = 50_000_000;
+const IMAGE_MAX_DECODE_SIDE: u32 = 16384;
+const IMAGE_MAX_DECODE_ALLOC: u64 = 256 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 85;
+const BLURHASH_COMPONENTS_X: u32 = 4;
+const BLURHASH_COMPONENTS_Y: u32 = 3;
+/// The blurhash only keeps a few components, so it's computed from a small
+/// copy of the image.
+const BLURHASH_SOURCE_SIZE: u32 = 64;
+
+/// Smaller copies stored next to every image, by name and longest side.
+/// Images that already fit don't get the variant.
+const IMAGE_VARIANTS: [(&str, u32); 2] = [("preview", 1280), ("thumbnail", 320)];
+
+/// Every frame of a GIF is decoded to get its duration, this caps the work
+/// as frames times pixels.
+const GIF_MAX_DECODED_PIXELS: u64 = 500_000_000;
+
+const ICON_SIZE: u32 = 400;
+const ICON_VARIANTS: [(&str, u32); 2] = [("thumbnail", 128), ("small", 48)];
+
+pub struct EncodedImage {
+    pub data: Vec<u8>,
+    pub mime_type: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct ProcessedImage {
+    /// What gets stored, GIFs keep their frames as they are with only the
+    /// metadata stripped, re-encoding them would drop their animation.
+    pub encoded: EncodedImage,
+    /// Of animated GIFs.
+    pub duration: Option<Duration>,
+    pub blurhash: String,
+    pub variants: Vec<(&'static str, EncodedImage)>,
+}
+
+fn limits() -> Limits {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(IMAGE_MAX_DECODE_SIDE);
+    limits.max_image_height = Some(IMAGE_MAX_DECODE_SIDE);
+    limits.max_alloc = Some(IMAGE_MAX_DECODE_ALLOC);
+    limits
+}
+
+fn decode_error(e: ImageError) -> MediaError {
+    match e {
+        ImageError::Limits(_) => MediaError::TooLarge,
+        ImageError::Unsupported(_) => MediaError::Unsupported,
+        _ => MediaError::Invalid,
+    }
+}
+
+/// Decodes the image with the EXIF orientation applied, the rest of the
+/// metadata is dropped with the source.
+fn decode<R: BufRead + Seek>(reader: R) -> Result<(DynamicImage, ImageFormat), MediaError> {
+    let mut reader = ImageReader::new(reader)
+        .with_guessed_format()
+        .map_err(|_| MediaError::Invalid)?;
+    let format = reader.format().ok_or(MediaError::Unsupported)?;
+    if !matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP
+    ) {
+        return Err(MediaError::Unsupported);
+    }
+    reader.limits(limits());
+
+    let mut decoder = reader.into_decoder().map_err(decode_error)?;
+    let (width, height) = decoder.dimensions();
+    if width == 0 || height == 0 {
+        return Err(MediaError::Invalid);
+    }
+    if width as u64 * height as u64 > IMAGE_MAX_PIXELS {
+        return Err(MediaError::TooLarge);
+    }
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
+    image.apply_orientation(orientation);
+    Ok((image, format))
+}
+
+/// Adds up the delays of the frames.
+fn gif_duration<R: BufRead + Seek>(reader: R) -> Result<Duration, MediaError> {
+    let mut decoder = GifDecoder::new(reader).map_err(decode_error)?;
+    decoder.set_limits(limits()).map_err(decode_error)?;
+    let (width, height) = decoder.dimensions();
+    let frame_pixels = (width as u64 * height as u64).max(1);
+
+    let mut duration = Duration::ZERO;
+    for (i, frame) in decoder.into_frames().enumerate() {
+        if (i as u64 + 1) * frame_pixels > GIF_MAX_DECODED_PIXELS {
+            return Err(MediaError::TooLarge);
+        }
+        let (numer, denom) = frame.map_err(decode_error)?.delay().numer_denom_ms();
+        duration += Duration::from_millis(numer as u64 / denom.max(1) as u64);
+        if duration > MEDIA_MAX_DURATION {
+            return Err(MediaError::TooLong);
+        }
+    }
+    Ok(duration)
+}
+
+/// Length of the color table following a screen or image descriptor with
+/// these packed fields.
+fn gif_color_table_len(fields: u8) -> usize {
+    match fields & 0x80 {
+        0 => 0,
+        _ => 3 << ((fields & 0x07) + 1),
+    }
+}
+
+/// Where the data sub-blocks starting at `pos` end.
+fn gif_skip_sub_blocks(data: &[u8], mut pos: usize) -> Result<usize, MediaError> {
+    loop {
+        let len = *data.get(pos).ok_or(MediaError::Invalid)? as usize;
+        pos += 1 + len;
+        if len == 0 {
+            return Ok(pos);
+        }
+    }
+}
+
+/// Copies the GIF without the extensions that can carry metadata, only the
+/// graphic control ones with the frame delays and the NETSCAPE one looping
+/// the animation are kept. Anything after the trailer is dropped too.
+fn strip_gif(data: &[u8]) -> Result<Vec<u8>, MediaError> {
+    // header and logical screen descriptor
+    let screen = data.get(..13).ok_or(MediaError::Invalid)?;
+    let mut pos = 13 + gif_color_table_len(screen[10]);
+    let mut stripped = data.get(..pos).ok_or(MediaError::Invalid)?.to_vec();
+
+    loop {
+        match data.get(pos) {
+            // extension
+            Some(0x21) => {
+                let label = *data.get(pos + 1).ok_or(MediaError::Invalid)?;
+                let end = gif_skip_sub_blocks(data, pos + 2)?;
+                let keep = match label {
+                    0xf9 => true,
+                    0xff => data.get(pos + 2..pos + 14) == Some(b"\x0bNETSCAPE2.0"),
+                    _ => false,
+                };
+                if keep {
+                    stripped.extend_from_slice(&data[pos..end]);
+                }
+                pos = end;
+            }
+            // image descriptor, its color table and LZW code size, then the
+            // image data
+            Some(0x2c) => {
+                let descriptor = data.get(pos..pos + 10).ok_or(MediaError::Invalid)?;
+                let end =
+                    gif_skip_sub_blocks(data, pos + 10 + gif_color_table_len(descriptor[9]) + 1)?;
+                stripped.extend_from_slice(&data[pos..end]);
+                pos = end;
+            }
+            // the trailer, GIFs cut before it still decode
+            Some(0x3b) | None => {
+                stripped.push(0x3b);
+                return Ok(stripped);
+            }
+            Some(_) => return Err(MediaError::Invalid),
+        }
+    }
+}
+
+/// PNG for images with transparency, JPEG for everything else.
+fn encode(image: &DynamicImage) -> Result<EncodedImage, MediaError> {
+    let mut data = vec![];
+    let mime_type = if image.color().has_alpha() {
+        image
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .map_err(|e| MediaError::Storage(e.to_string()))?;
+        "image/png"
+    } else {
+        let encoder = JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY);
+        image
+            .to_rgb8()
+            .write_with_encoder(encoder)
+            .map_err(|e| MediaError::Storage(e.to_string()))?;
+        "image/jpeg"
+    };
+    Ok(EncodedImage {
+        data,
+        mime_type,
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+fn blurhash(image: &DynamicImage) -> Result<String, MediaError> {
+    let small = image
+        .thumbnail(BLURHASH_SOURCE_SIZE, BLURHASH_SOURCE_SIZE)
+        .to_rgba8();
+    blurhash::encode(
+        BLURHASH_COMPONENTS_X,
+        BLURHASH_COMPONENTS_Y,
+        small.width(),
+        small.height(),
+        small.as_raw(),
+    )
+    .map_err(|e| MediaError::Storage(e.to_string()))
+}
+
+/// Re-encodes the image without its metadata, scaled down to
+/// [`IMAGE_MAX_DIMENSION`], and generates its variants.
+/// GIFs are checked frame by frame, stripped of their metadata and get a
+/// `poster` variant with their first frame.
+pub fn process_image<R: BufRead + Seek>(mut reader: R) -> Result<ProcessedImage, MediaError> {
+    let (mut image, format) = decode(&mut reader)?;
+
+    let mut variants = vec![];
+    let mut duration = None;
+    let encoded = if format == ImageFormat::Gif {
+        if image.width() > IMAGE_MAX_DIMENSION || image.height() > IMAGE_MAX_DIMENSION {
+            return Err(MediaError::TooLarge);
+        }
+        reader.rewind().map_err(|_| MediaError::Invalid)?;
+        let mut data = vec![];
+        reader
+            .read_to_end(&mut data)
+            .map_err(|_| MediaError::Invalid)?;
+        duration = Some(gif_duration(Cursor::new(&data))?);
+        variants.push(("poster", encode(&image)?));
+        EncodedImage {
+            data: strip_gif(&data)?,
+            mime_type: "image/gif",
+            width: image.width(),
+            height: image.height(),
+        }
+    } else {
+        if image.width() > IMAGE_MAX_DIMENSION || image.height() > IMAGE_MAX_DIMENSION {
+            image = image.resize(
+                IMAGE_MAX_DIMENSION,
+                IMAGE_MAX_DIMENSION,
+                FilterType::Lanczos3,
+            );
+        }
+        encode(&image)?
+    };
+
+    for (name, size) in IMAGE_VARIANTS {
+        if image.width() > size || image.height() > size {
+            variants.push((
+                name,
+                encode(&image.resize(size, size, FilterType::Triangle))?,
+            ));
+        }
+    }
+
+    Ok(ProcessedImage {
+        encoded,
+        duration,
+        blurhash: blurhash(&image)?,
+        variants,
+    })
+}
+
+/// Crops the image to a centered square of [`ICON_SIZE`], GIFs keep their
+/// first frame.
+pub fn process_icon<R: BufRead + Seek>(reader: R) -> Result<ProcessedImage, MediaError> {
+    let (image, _) = decode(reader)?;
+
+    let side = image.width().min(image.height());
+    let image = image
+        .crop_imm(
+            (image.width() - side) / 2,
+            (image.height() - side) / 2,
+            side,
+            side,
+        )
+        .resize_exact(ICON_SIZE, ICON_SIZE, FilterType::Lanczos3);
+
+    let mut variants = vec![];
+    for (name, size) in ICON_VARIANTS {
+        variants.push((
+            name,
+            encode(&image.resize_exact(size, size, FilterType::Triangle))?,
+        ));
+    }
+
+    Ok(ProcessedImage {
+        encoded: encode(&image)?,
+        duration: None,
+        blurhash: blurhash(&image)?,
+        variants,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{
+        codecs::gif::{GifEncoder, Repeat},
+        Delay, Frame, Rgba, RgbaImage,
+    };
+
+    use super::*;
+
+    fn contains(data: &[u8], needle: &[u8]) -> bool {
+        data.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn strips_gif_metadata() {
+        let mut gif = vec![];
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            encoder.set_repeat(Repeat::Infinite).unwrap();
+            for color in [[255, 0, 0, 255], [0, 0, 255, 255]] {
+                let frame = RgbaImage::from_pixel(8, 8, Rgba(color));
+                encoder
+                    .encode_frame(Frame::from_parts(
+                        frame,
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(100, 1),
+                    ))
+                    .unwrap();
+            }
+        }
+        // a comment and an XMP packet before the frames, junk after the trailer
+        let pos = 13 + gif_color_table_len(gif[10]);
+        let mut metadata = b"\x21\xfe\x0bsecret note\x00".to_vec();
+        metadata.extend_from_slice(b"\x21\xff\x0bXMP DataXMP\x0aGPS 48N 2E\x00");
+        gif.splice(pos..pos, metadata);
+        gif.extend_from_slice(b"trailing junk");
+
+        let processed = process_image(Cursor::new(gif)).unwrap();
+        let data = &processed.encoded.data;
+        assert_eq!(processed.encoded.mime_type, "image/gif");
+        assert!(!contains(data, b"secret note"));
+        assert!(!contains(data, b"XMP Data"));
+        assert!(!contains(data, b"GPS"));
+        assert!(!contains(data, b"trailing junk"));
+        assert!(contains(data, b"NETSCAPE2.0"));
+        assert_eq!(data.last(), Some(&0x3b));
+
+        assert_eq!(processed.duration, Some(Duration::from_millis(200)));
+        let frames = GifDecoder::new(Cursor::new(data))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].buffer().get_pixel(0, 0), &Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn rejects_broken_gifs() {
+        assert!(strip_gif(b"GIF89a").is_err());
+        let mut gif = b"GIF89a\x01\x00\x01\x00\x00\x00\x00".to_vec();
+        gif.push(0x42);
+        assert!(strip_gif(&gif).is_err());
+    }
+}
@@ -0,0 +1,609 @@
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+use tokio::{process::Command, time::timeout};
+
+use super::{MediaError, MEDIA_MAX_DURATION};
+
+/// The `moov` box of an MP4 and the `Info` and `Tracks` elements of a WebM
+/// are read into memory, anything bigger isn't a short clip.
+const CONTAINER_MAX_HEADER_BYTES: u64 = 8 * 1024 * 1024;
+const POSTER_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    pub duration: Duration,
+}
+
+fn invalid<E>(_: E) -> MediaError {
+    MediaError::Invalid
+}
+
+fn be_uint(data: &[u8], offset: usize, len: usize) -> Result<u64, MediaError> {
+    let end = offset.checked_add(len).ok_or(MediaError::Invalid)?;
+    let bytes = data.get(offset..end).ok_or(MediaError::Invalid)?;
+    Ok(bytes.iter().fold(0, |n, b| (n << 8) | *b as u64))
+}
+
+/// Seconds as a duration, durations too big for it are too long anyway.
+fn duration_from_secs(secs: f64) -> Result<Duration, MediaError> {
+    Duration::try_from_secs_f64(secs).map_err(|_| MediaError::TooLong)
+}
+
+/// Checks the container and reads what's needed from it, the streams
+/// themselves are only looked at by ffmpeg for the poster. Videos longer than
+/// [`MEDIA_MAX_DURATION`] are refused.
+pub fn probe_video<R: Read + Seek>(reader: R, mime_type: &str) -> Result<VideoInfo, MediaError> {
+    let info = match mime_type {
+        "video/mp4" => probe_mp4(reader)?,
+        "video/webm" => probe_webm(reader)?,
+        _ => return Err(MediaError::Unsupported),
+    };
+    if info.width == 0 || info.height == 0 || info.duration.is_zero() {
+        return Err(MediaError::Invalid);
+    }
+    if info.duration > MEDIA_MAX_DURATION {
+        return Err(MediaError::TooLong);
+    }
+    Ok(info)
+}
+
+/// The type and content of an MP4 box.
+type Mp4Box<'a> = ([u8; 4], &'a [u8]);
+
+fn mp4_boxes(data: &[u8]) -> Result<Vec<Mp4Box<'_>>, MediaError> {
+    let mut boxes = vec![];
+    let mut pos = 0;
+    while pos < data.len() {
+        let size = be_uint(data, pos, 4)?;
+        let kind: [u8; 4] = data
+            .get(pos + 4..pos + 8)
+            .ok_or(MediaError::Invalid)?
+            .try_into()
+            .map_err(invalid)?;
+        let (header_len, size) = match size {
+            0 => (8, data.len() - pos),
+            1 => (
+                16,
+                usize::try_from(be_uint(data, pos + 8, 8)?).map_err(invalid)?,
+            ),
+            size => (8, size as usize),
+        };
+        if size < header_len {
+            return Err(MediaError::Invalid);
+        }
+        let end = pos
+            .checked_add(size)
+            .filter(|end| *end <= data.len())
+            .ok_or(MediaError::Invalid)?;
+        boxes.push((kind, &data[pos + header_len..end]));
+        pos = end;
+    }
+    Ok(boxes)
+}
+
+fn mp4_child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Result<Option<&'a [u8]>, MediaError> {
+    Ok(mp4_boxes(data)?
+        .into_iter()
+        .find(|(k, _)| k == kind)
+        .map(|(_, content)| content))
+}
+
+fn probe_mp4<R: Read + Seek>(mut reader: R) -> Result<VideoInfo, MediaError> {
+    let len = reader.seek(SeekFrom::End(0)).map_err(invalid)?;
+
+    // the top level is walked on the file, mdat holds the streams and is
+    // skipped over
+    let mut moov = None;
+    let mut has_mdat = false;
+    let mut pos = 0;
+    while pos < len {
+        reader.seek(SeekFrom::Start(pos)).map_err(invalid)?;
+        let mut header = [0; 16];
+        reader.read_exact(&mut header[..8]).map_err(invalid)?;
+        let kind: [u8; 4] = [header[4], header[5], header[6], header[7]];
+        let (header_len, size) = match be_uint(&header, 0, 4)? {
+            0 => (8, len - pos),
+            1 => {
+                reader.read_exact(&mut header[8..]).map_err(invalid)?;
+                (16, be_uint(&header, 8, 8)?)
+            }
+            size => (8, size),
+        };
+        if size < header_len || (pos == 0 && &kind != b"ftyp") {
+            return Err(MediaError::Invalid);
+        }
+        let end = pos
+            .checked_add(size)
+            .filter(|end| *end <= len)
+            .ok_or(MediaError::Invalid)?;
+        match &kind {
+            b"moov" => {
+                if size - header_len > CONTAINER_MAX_HEADER_BYTES {
+                    return Err(MediaError::Invalid);
+                }
+                let mut content = vec![0; (size - header_len) as usize];
+                reader.read_exact(&mut content).map_err(invalid)?;
+                moov = Some(content);
+            }
+            b"mdat" => has_mdat = true,
+            _ => {}
+        }
+        pos = end;
+    }
+    let moov = moov.ok_or(MediaError::Invalid)?;
+    if !has_mdat {
+        return Err(MediaError::Invalid);
+    }
+
+    let mvhd = mp4_child(&moov, b"mvhd")?.ok_or(MediaError::Invalid)?;
+    let (timescale, mut duration) = match mvhd.first() {
+        Some(1) => (be_uint(mvhd, 20, 4)?, be_uint(mvhd, 24, 8)?),
+        _ => (be_uint(mvhd, 12, 4)?, be_uint(mvhd, 16, 4)?),
+    };
+    // fragmented files keep the duration in mvex/mehd
+    if duration == 0 {
+        if let Some(mehd) = mp4_child(&moov, b"mvex")?
+            .map(|mvex| mp4_child(mvex, b"mehd"))
+            .transpose()?
+            .flatten()
+        {
+            duration = match mehd.first() {
+                Some(1) => be_uint(mehd, 4, 8)?,
+                _ => be_uint(mehd, 4, 4)?,
+            };
+        }
+    }
+    if timescale == 0 || duration == 0 || duration == u32::MAX as u64 {
+        return Err(MediaError::Invalid);
+    }
+
+    for (kind, trak) in mp4_boxes(&moov)? {
+        if &kind != b"trak" {
+            continue;
+        }
+        let handler = mp4_child(trak, b"mdia")?
+            .map(|mdia| mp4_child(mdia, b"hdlr"))
+            .transpose()?
+            .flatten()
+            .and_then(|hdlr| hdlr.get(8..12));
+        if handler != Some(b"vide") {
+            continue;
+        }
+        let tkhd = mp4_child(trak, b"tkhd")?.ok_or(MediaError::Invalid)?;
+        // width and height are 16.16 fixed point after the matrix
+        let offset = match tkhd.first() {
+            Some(1) => 88,
+            _ => 76,
+        };
+        return Ok(VideoInfo {
+            width: (be_uint(tkhd, offset, 4)? >> 16) as u32,
+            height: (be_uint(tkhd, offset + 4, 4)? >> 16) as u32,
+            duration: duration_from_secs(duration as f64 / timescale as f64)?,
+        });
+    }
+    Err(MediaError::Invalid)
+}
+
+const EBML_HEADER: u64 = 0x1A45DFA3;
+const EBML_DOC_TYPE: u64 = 0x4282;
+const WEBM_SEGMENT: u64 = 0x18538067;
+const WEBM_INFO: u64 = 0x1549A966;
+const WEBM_TIMECODE_SCALE: u64 = 0x2AD7B1;
+const WEBM_DURATION: u64 = 0x4489;
+const WEBM_TRACKS: u64 = 0x1654AE6B;
+const WEBM_TRACK_ENTRY: u64 = 0xAE;
+const WEBM_TRACK_TYPE: u64 = 0x83;
+const WEBM_TRACK_TYPE_VIDEO: u64 = 1;
+const WEBM_VIDEO: u64 = 0xE0;
+const WEBM_PIXEL_WIDTH: u64 = 0xB0;
+const WEBM_PIXEL_HEIGHT: u64 = 0xBA;
+const WEBM_CLUSTER: u64 = 0x1F43B675;
+const WEBM_DEFAULT_TIMECODE_SCALE: u64 = 1_000_000;
+
+/// Reads an EBML variable length integer, ids keep their length marker and
+/// sizes don't. `None` for sizes that are unknown.
+fn ebml_vint<R: Read>(reader: &mut R, is_id: bool) -> Result<Option<u64>, MediaError> {
+    let mut first = [0];
+    reader.read_exact(&mut first).map_err(invalid)?;
+    let len = first[0].leading_zeros() as usize + 1;
+    if len > if is_id { 4 } else { 8 } {
+        return Err(MediaError::Invalid);
+    }
+    let mut value = if is_id {
+        first[0] as u64
+    } else {
+        first[0] as u64 & (0xFF >> len)
+    };
+    let mut rest = [0; 7];
+    reader.read_exact(&mut rest[..len - 1]).map_err(invalid)?;
+    for b in &rest[..len - 1] {
+        value = (value << 8) | *b as u64;
+    }
+    if !is_id && value == (1 << (7 * len)) - 1 {
+        return Ok(None);
+    }
+    Ok(Some(value))
+}
+
+/// Reads the id and size of the next element.
+fn ebml_element<R: Read>(reader: &mut R) -> Result<(u64, Option<u64>), MediaError> {
+    let id = ebml_vint(reader, true)?.ok_or(MediaError::Invalid)?;
+    let size = ebml_vint(reader, false)?;
+    Ok((id, size))
+}
+
+/// Elements in `data` as their id and content.
+fn ebml_children(data: &[u8]) -> Result<Vec<(u64, &[u8])>, MediaError> {
+    let mut children = vec![];
+    let mut cursor = Cursor::new(data);
+    while (cursor.position() as usize) < data.len() {
+        let (id, size) = ebml_element(&mut cursor)?;
+        let start = cursor.position() as usize;
+        let size = usize::try_from(size.ok_or(MediaError::Invalid)?).map_err(invalid)?;
+        let end = start.checked_add(size).ok_or(MediaError::Invalid)?;
+        let content = data.get(start..end).ok_or(MediaError::Invalid)?;
+        children.push((id, content));
+        cursor.set_position(end as u64);
+    }
+    Ok(children)
+}
+
+fn ebml_child(data: &[u8], id: u64) -> Result<Option<&[u8]>, MediaError> {
+    Ok(ebml_children(data)?
+        .into_iter()
+        .find(|(i, _)| *i == id)
+        .map(|(_, content)| content))
+}
+
+fn ebml_uint(data: &[u8]) -> Result<u64, MediaError> {
+    if data.len() > 8 {
+        return Err(MediaError::Invalid);
+    }
+    be_uint(data, 0, data.len())
+}
+
+fn ebml_float(data: &[u8]) -> Result<f64, MediaError> {
+    match data.len() {
+        4 => Ok(f32::from_be_bytes(data.try_into().map_err(invalid)?) as f64),
+        8 => Ok(f64::from_be_bytes(data.try_into().map_err(invalid)?)),
+        _ => Err(MediaError::Invalid),
+    }
+}
+
+fn read_element<R: Read>(reader: &mut R, size: Option<u64>) -> Result<Vec<u8>, MediaError> {
+    let size = size.ok_or(MediaError::Invalid)?;
+    if size > CONTAINER_MAX_HEADER_BYTES {
+        return Err(MediaError::Invalid);
+    }
+    let mut content = vec![0; size as usize];
+    reader.read_exact(&mut content).map_err(invalid)?;
+    Ok(content)
+}
+
+fn probe_webm<R: Read + Seek>(mut reader: R) -> Result<VideoInfo, MediaError> {
+    let len = reader.seek(SeekFrom::End(0)).map_err(invalid)?;
+    reader.rewind().map_err(invalid)?;
+
+    let (id, size) = ebml_element(&mut reader)?;
+    if id != EBML_HEADER {
+        return Err(MediaError::Invalid);
+    }
+    let header = read_element(&mut reader, size)?;
+    if ebml_child(&header, EBML_DOC_TYPE)? != Some(b"webm") {
+        return Err(MediaError::Unsupported);
+    }
+
+    let (id, size) = ebml_element(&mut reader)?;
+    if id != WEBM_SEGMENT {
+        return Err(MediaError::Invalid);
+    }
+    // live recordings leave the size of the segment unknown
+    let segment_end = match size {
+        Some(size) => reader
+            .stream_position()
+            .map_err(invalid)?
+            .checked_add(size)
+            .ok_or(MediaError::Invalid)?,
+        None => len,
+    };
+
+    let mut info = None;
+    let mut tracks = None;
+    while info.is_none() || tracks.is_none() {
+        let pos = reader.stream_position().map_err(invalid)?;
+        if pos >= segment_end {
+            break;
+        }
+        let (id, size) = ebml_element(&mut reader)?;
+        match id {
+            WEBM_INFO => info = Some(read_element(&mut reader, size)?),
+            WEBM_TRACKS => tracks = Some(read_element(&mut reader, size)?),
+            // the header elements come before the first cluster
+            WEBM_CLUSTER => break,
+            _ => {
+                let size = i64::try_from(size.ok_or(MediaError::Invalid)?).map_err(invalid)?;
+                reader.seek(SeekFrom::Current(size)).map_err(invalid)?;
+            }
+        }
+    }
+    let info = info.ok_or(MediaError::Invalid)?;
+    let tracks = tracks.ok_or(MediaError::Invalid)?;
+
+    let timecode_scale = match ebml_child(&info, WEBM_TIMECODE_SCALE)? {
+        Some(scale) => ebml_uint(scale)?,
+        None => WEBM_DEFAULT_TIMECODE_SCALE,
+    };
+    let duration = ebml_float(ebml_child(&info, WEBM_DURATION)?.ok_or(MediaError::Invalid)?)?;
+    let duration = duration * timecode_scale as f64 / 1e9;
+    if !duration.is_finite() || duration <= 0.0 {
+        return Err(MediaError::Invalid);
+    }
+
+    for (id, entry) in ebml_children(&tracks)? {
+        if id != WEBM_TRACK_ENTRY {
+            continue;
+        }
+        let track_type = ebml_child(entry, WEBM_TRACK_TYPE)?
+            .map(ebml_uint)
+            .transpose()?;
+        if track_type != Some(WEBM_TRACK_TYPE_VIDEO) {
+            continue;
+        }
+        let video = ebml_child(entry, WEBM_VIDEO)?.ok_or(MediaError::Invalid)?;
+        let width = ebml_child(video, WEBM_PIXEL_WIDTH)?.ok_or(MediaError::Invalid)?;
+        let height = ebml_child(video, WEBM_PIXEL_HEIGHT)?.ok_or(MediaError::Invalid)?;
+        return Ok(VideoInfo {
+            width: ebml_uint(width)?.try_into().map_err(invalid)?,
+            height: ebml_uint(height)?.try_into().map_err(invalid)?,
+            duration: duration_from_secs(duration)?,
+        });
+    }
+    Err(MediaError::Invalid)
+}
+
+/// First frame of the video as a PNG, decoded by ffmpeg (`FFMPEG_PATH`,
+/// `ffmpeg` by default).
+pub async fn extract_poster(path: &Path) -> Result<Vec<u8>, MediaError> {
+    let ffmpeg = dotenv::var("FFMPEG_PATH").unwrap_or("ffmpeg".to_string());
+    let output = Command::new(ffmpeg)
+        .args(["-nostdin", "-v", "error", "-i"])
+        .arg(path)
+        .args(["-frames:v", "1", "-f", "image2pipe", "-c:v", "png", "-"])
+        .kill_on_drop(true)
+        .output();
+    let output = timeout(POSTER_TIMEOUT, output)
+        .await
+        .map_err(|_| MediaError::Storage("ffmpeg timed out".to_string()))?
+        .map_err(|e| MediaError::Storage(format!("unable to run ffmpeg: {e}")))?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(MediaError::Storage(format!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(kind: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut data = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(content);
+        data
+    }
+
+    /// A 640x360 MP4 with one video track, laid out like the encoders do.
+    fn mp4(timescale: u32, duration: u32) -> Vec<u8> {
+        let mut mvhd = vec![0; 100];
+        mvhd[12..16].copy_from_slice(&timescale.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&duration.to_be_bytes());
+        let mut tkhd = vec![0; 84];
+        tkhd[76..80].copy_from_slice(&(640u32 << 16).to_be_bytes());
+        tkhd[80..84].copy_from_slice(&(360u32 << 16).to_be_bytes());
+        let mut hdlr = vec![0; 24];
+        hdlr[8..12].copy_from_slice(b"vide");
+        let trak = [
+            mp4_box(b"tkhd", &tkhd),
+            mp4_box(b"mdia", &mp4_box(b"hdlr", &hdlr)),
+        ]
+        .concat();
+        let moov = [mp4_box(b"mvhd", &mvhd), mp4_box(b"trak", &trak)].concat();
+        [
+            mp4_box(b"ftyp", b"isom\0\0\0\0isom"),
+            mp4_box(b"moov", &moov),
+            mp4_box(b"mdat", &[0; 16]),
+        ]
+        .concat()
+    }
+
+    fn probe(data: Vec<u8>, mime_type: &str) -> Result<VideoInfo, MediaError> {
+        probe_video(Cursor::new(data), mime_type)
+    }
+
+    #[test]
+    fn probes_mp4() {
+        let info = probe(mp4(1000, 12_500), "video/mp4").unwrap();
+        assert_eq!((info.width, info.height), (640, 360));
+        assert_eq!(info.duration, Duration::from_millis(12_500));
+    }
+
+    #[test]
+    fn rejects_truncated_mp4() {
+        let data = mp4(1000, 12_500);
+        for len in [4, 20, data.len() / 2, data.len() - 1] {
+            assert!(matches!(
+                probe(data[..len].to_vec(), "video/mp4"),
+                Err(MediaError::Invalid)
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_mp4_boxes() {
+        // a 64-bit size that wraps the position back to the start of the file
+        let mut data = mp4(1000, 12_500);
+        let pos = data.len() as u64;
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(b"free");
+        data.extend_from_slice(&(u64::MAX - pos + 1).to_be_bytes());
+        data.extend_from_slice(&[0; 20]);
+        assert!(matches!(probe(data, "video/mp4"), Err(MediaError::Invalid)));
+
+        // same inside moov, read from memory
+        let mut moov_child = 1u32.to_be_bytes().to_vec();
+        moov_child.extend_from_slice(b"free");
+        moov_child.extend_from_slice(&u64::MAX.to_be_bytes());
+        let data = [
+            mp4_box(b"ftyp", b"isom\0\0\0\0isom"),
+            mp4_box(b"moov", &moov_child),
+            mp4_box(b"mdat", &[0; 16]),
+        ]
+        .concat();
+        assert!(matches!(probe(data, "video/mp4"), Err(MediaError::Invalid)));
+    }
+
+    #[test]
+    fn rejects_mp4_without_timescale() {
+        assert!(matches!(
+            probe(mp4(0, 12_500), "video/mp4"),
+            Err(MediaError::Invalid)
+        ));
+    }
+
+    #[test]
+    fn rejects_long_mp4() {
+        assert!(matches!(
+            probe(mp4(1000, 200_000), "video/mp4"),
+            Err(MediaError::TooLong)
+        ));
+
+        // version 1 mvhd with a duration no Duration can hold
+        let mut data = mp4(1, 1);
+        let mvhd = data
+            .windows(4)
+            .position(|w| w == b"mvhd")
+            .expect("the mvhd box")
+            + 4;
+        data[mvhd] = 1;
+        data[mvhd + 20..mvhd + 24].copy_from_slice(&1u32.to_be_bytes());
+        data[mvhd + 24..mvhd + 32].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(matches!(probe(data, "video/mp4"), Err(MediaError::TooLong)));
+    }
+
+    /// The id and an 8 byte size, like muxers write for elements they fill
+    /// in later.
+    fn ebml(id: u64, content: &[u8]) -> Vec<u8> {
+        let id_len = 8 - id.leading_zeros() as usize / 8;
+        let mut data = id.to_be_bytes()[8 - id_len..].to_vec();
+        data.push(0x01);
+        data.extend_from_slice(&(content.len() as u64).to_be_bytes()[1..]);
+        data.extend_from_slice(content);
+        data
+    }
+
+    /// A 640x360 WebM, `duration` in units of `timecode_scale` nanoseconds.
+    fn webm(timecode_scale: u32, duration: f64) -> Vec<u8> {
+        let info = [
+            ebml(WEBM_TIMECODE_SCALE, &timecode_scale.to_be_bytes()),
+            ebml(WEBM_DURATION, &duration.to_be_bytes()),
+        ]
+        .concat();
+        let video = [
+            ebml(WEBM_PIXEL_WIDTH, &640u16.to_be_bytes()),
+            ebml(WEBM_PIXEL_HEIGHT, &360u16.to_be_bytes()),
+        ]
+        .concat();
+        let entry = [ebml(WEBM_TRACK_TYPE, &[1]), ebml(WEBM_VIDEO, &video)].concat();
+        let segment = [
+            ebml(WEBM_INFO, &info),
+            ebml(WEBM_TRACKS, &ebml(WEBM_TRACK_ENTRY, &entry)),
+            ebml(WEBM_CLUSTER, &[0; 16]),
+        ]
+        .concat();
+        [
+            ebml(EBML_HEADER, &ebml(EBML_DOC_TYPE, b"webm")),
+            ebml(WEBM_SEGMENT, &segment),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn probes_webm() {
+        let info = probe(webm(1_000_000, 12_500.0), "video/webm").unwrap();
+        assert_eq!((info.width, info.height), (640, 360));
+        assert_eq!(info.duration, Duration::from_millis(12_500));
+    }
+
+    #[test]
+    fn rejects_truncated_webm() {
+        let data = webm(1_000_000, 12_500.0);
+        // the cluster at the end isn't needed
+        for len in [3, 20, data.len() / 2, data.len() - 40] {
+            assert!(matches!(
+                probe(data[..len].to_vec(), "video/webm"),
+                Err(MediaError::Invalid)
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_webm_elements() {
+        // a child of Info claiming more than Info holds
+        let info = [
+            ebml(WEBM_DURATION, &12_500f64.to_be_bytes()),
+            vec![
+                0x2A, 0xD7, 0xB1, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+            ],
+        ]
+        .concat();
+        let data = [
+            ebml(EBML_HEADER, &ebml(EBML_DOC_TYPE, b"webm")),
+            ebml(WEBM_SEGMENT, &ebml(WEBM_INFO, &info)),
+        ]
+        .concat();
+        assert!(matches!(
+            probe(data, "video/webm"),
+            Err(MediaError::Invalid)
+        ));
+
+        // a skipped element past the end of the file
+        let mut data = [
+            ebml(EBML_HEADER, &ebml(EBML_DOC_TYPE, b"webm")),
+            vec![0x18, 0x53, 0x80, 0x67, 0xff],
+        ]
+        .concat();
+        data.extend_from_slice(&[0xEC, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe]);
+        assert!(matches!(
+            probe(data, "video/webm"),
+            Err(MediaError::Invalid)
+        ));
+    }
+
+    #[test]
+    fn rejects_webm_without_timecode_scale() {
+        assert!(matches!(
+            probe(webm(0, 12_500.0), "video/webm"),
+            Err(MediaError::Invalid)
+        ));
+    }
+
+    #[test]
+    fn rejects_long_webm() {
+        assert!(matches!(
+            probe(webm(1_000_000, 200_000.0), "video/webm"),
+            Err(MediaError::TooLong)
+        ));
+        assert!(matches!(
+            probe(webm(1_000_000, 1e300), "video/webm"),
+            Err(MediaError::TooLong)
+        ));
+    }
+}
@@ -1,5 +1,13 @@
 pub mod auth;
+pub mod blocks;
 pub mod change;
+pub mod drafts;
+pub mod follow_requests;
+pub mod media;
+pub mod mutes;
+pub mod scheduled;
+pub mod suggestions;
+pub mod trending;
 pub mod types;
 pub mod user;
 pub mod user_get;
@@ -0,0 +1,126 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::{
+    http::{CookieJar, Status},
+    response::status::Custom,
+    serde::json::Json,
+};
+use sqlx::{Pool, Postgres};
+
+use crate::{auth::validate_jwt, database, media::media_url};
+
+use super::types::{DataResponse, UpdatedFollowData};
+
+/// The id of the user, `NotFound` if they don't exist.
+pub async fn get_user_id(
+    user_at: &str,
+    pool: &Pool<Postgres>,
+) -> Result<i32, Custom<&'static str>> {
+    if !database::user_exists(user_at, pool).await {
+        return Err(Custom(Status::NotFound, "User doesn't exist"));
+    }
+    let Ok(email) = database::get_email_from_user_at(user_at, pool).await else {
+        return Err(Custom(Status::InternalServerError, "InternalServerError"));
+    };
+    let Ok(id) = database::get_id_from_email(&email, pool).await else {
+        return Err(Custom(Status::InternalServerError, "InternalServerError"));
+    };
+    Ok(id)
+}
+
+#[post("/user/block/<user_at>")]
+pub async fn block_user(user_at: &str, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    if s.user_at == user_at {
+        return Custom(Status::BadRequest, "You can't block yourself");
+    }
+    let pool = database::connect_db().await;
+
+    let blocked_id = match get_user_id(user_at, &pool).await {
+        Ok(id) => id,
+        Err(e) => return e,
+    };
+
+    if database::block_user(&s.id, &blocked_id, &date, &pool)
+        .await
+        .is_err()
+    {
+        return Custom(Status::InternalServerError, "InternalServerError");
+    }
+
+    Custom(Status::Ok, "User blocked")
+}
+
+#[delete("/user/block/<user_at>")]
+pub async fn unblock_user(user_at: &str, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    let blocked_id = match get_user_id(user_at, &pool).await {
+        Ok(id) => id,
+        Err(e) => return e,
+    };
+
+    if database::unblock_user(&s.id, &blocked_id, &pool)
+        .await
+        .is_err()
+    {
+        return Custom(Status::InternalServerError, "InternalServerError");
+    }
+
+    Custom(Status::Ok, "User unblocked")
+}
+
+#[get("/user/blocks", format = "application/json")]
+pub async fn fetch_blocks(
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<UpdatedFollowData>, &'static str>> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let pool = database::connect_db().await;
+
+    let Ok(blocked) = database::get_blocked_list(&s.id, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(blocked
+            .into_iter()
+            .map(|b| UpdatedFollowData {
+                user_at: b.userat,
+                username: b.username,
+                icon: media_url(b.icon_id),
+            })
+            .collect())),
+    }
+}
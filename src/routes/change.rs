@@ -1,14 +1,20 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use rocket::{http::CookieJar, http::Status, response::status::Custom, serde::json::Json};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     auth::{create_jwt, hash::hash_str, validate_jwt, Sub},
     database::{self, email_exists, user_exists, user_has_credentials, verify_password},
+    media::{make_icon, resolve_media},
     validate_email, validate_password, validate_user_at, validate_user_name, ValidField,
-    BIO_MAX_LEN,
+    BIO_MAX_LEN, POST_EDIT_WINDOW_DEFAULT_MINUTES,
 };
 
-use super::types::{EmailChangeData, PasswordChangeData, ProfileUpdate, UserAtChangeData};
+use super::{
+    types::{EmailChangeData, PasswordChangeData, ProfileUpdate, UserAtChangeData},
+    user::{validate_attachments, validate_content_warning, AttachmentData, SensitiveMedia},
+};
 
 #[patch(
     "/user/change/password",
@@ -251,10 +257,41 @@ pub async fn follow_user(data: Json<FollowData>, cookies: &CookieJar<'_>) -> Cus
     };
 
     if data.follow {
-        let Ok(..) = database::follow_user(&follow_target_id, &sub.id, &pool).await else {
+        match database::is_blocked(&follow_target_id, &sub.id, &pool).await {
+            Ok(false) => {}
+            Ok(true) => return Custom(Status::Forbidden, "You can't follow this user"),
+            Err(..) => return Custom(Status::InternalServerError, "InternalServerError"),
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("We're in 1969??")
+            .as_millis() as i64;
+
+        // private accounts have to approve their followers first
+        let Ok(is_private) = database::is_private(&follow_target_id, &pool).await else {
+            return Custom(Status::InternalServerError, "InternalServerError");
+        };
+        let Ok(is_following) = database::is_following(&follow_target_id, &sub.id, &pool).await
+        else {
+            return Custom(Status::InternalServerError, "InternalServerError");
+        };
+        if is_private && !is_following {
+            let Ok(..) = database::request_follow(&follow_target_id, &sub.id, &now, &pool).await
+            else {
+                return Custom(Status::InternalServerError, "InternalServerError");
+            };
+            return Custom(Status::Accepted, "Follow requested");
+        }
+
+        let Ok(..) = database::follow_user(&follow_target_id, &sub.id, &now, &pool).await else {
             return Custom(Status::InternalServerError, "InternalServerError");
         };
     } else {
+        // also cancels a pending request
+        let Ok(..) = database::remove_follow_request(&follow_target_id, &sub.id, &pool).await
+        else {
+            return Custom(Status::InternalServerError, "InternalServerError");
+        };
         let Ok(..) = database::unfollow_user(&follow_target_id, &sub.id, &pool).await else {
             return Custom(Status::InternalServerError, "InternalServerError");
         };
@@ -297,6 +334,20 @@ pub async fn change_profile(
 
     let pool = database::connect_db().await;
 
+    // an empty icon removes it
+    let icon_id = if profile_data.icon.is_empty() {
+        None
+    } else {
+        let icon = match resolve_media(&profile_data.icon, &pool).await {
+            Ok(id) => make_icon(&id, &pool).await,
+            Err(e) => Err(e),
+        };
+        match icon {
+            Ok(id) => Some(id),
+            Err(e) => return e.into(),
+        }
+    };
+
     if database::change_bio(&s.email, &profile_data.bio, &pool)
         .await
         .is_err()
@@ -311,7 +362,7 @@ pub async fn change_profile(
         return Custom(Status::InternalServerError, "InternalServerError");
     }
 
-    if database::change_icon(&s.email, &profile_data.icon, &pool)
+    if database::change_icon(&s.email, &icon_id, &pool)
         .await
         .is_err()
     {
@@ -321,10 +372,81 @@ pub async fn change_profile(
     Custom(Status::Ok, "Ok")
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PrivateData {
+    pub private: bool,
+}
+
+#[patch("/user/change/private", format = "application/json", data = "<data>")]
+pub async fn change_private(
+    data: Json<PrivateData>,
+    cookies: &CookieJar<'_>,
+) -> Custom<&'static str> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    if database::change_private(&s.id, &data.private, &date, &pool)
+        .await
+        .is_err()
+    {
+        return Custom(Status::InternalServerError, "InternalServerError");
+    }
+    Custom(Status::Ok, "Ok")
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SensitiveMediaData {
+    #[serde(rename = "sensitiveMedia")]
+    pub sensitive_media: SensitiveMedia,
+}
+
+#[patch(
+    "/user/change/sensitive-media",
+    format = "application/json",
+    data = "<data>"
+)]
+pub async fn change_sensitive_media(
+    data: Json<SensitiveMediaData>,
+    cookies: &CookieJar<'_>,
+) -> Custom<&'static str> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    if database::change_sensitive_media(&s.id, &data.sensitive_media, &pool)
+        .await
+        .is_err()
+    {
+        return Custom(Status::InternalServerError, "InternalServerError");
+    }
+    Custom(Status::Ok, "Ok")
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct EditPostData {
     pub text: String,
     pub image: String,
+    #[serde(rename = "contentWarning", default)]
+    pub content_warning: Option<String>,
+    #[serde(default)]
+    pub sensitive: bool,
+    #[serde(default)]
+    pub attachments: Vec<AttachmentData>,
 }
 
 #[patch(
@@ -353,10 +475,59 @@ pub async fn edit_post(
         return Custom(Status::Forbidden, "forbidden");
     }
 
-    let data = data.into_inner();
+    let Ok(post) = database::get_post_by_id(&pool, &post_id).await else {
+        return Custom(Status::NotFound, "Post not found");
+    };
+
+    if post.owner_id != s.id {
+        return Custom(Status::Forbidden, "forbidden");
+    }
+
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    if date - post.unix_time > edit_window_millis() {
+        return Custom(Status::Forbidden, "Edit window has expired");
+    }
+
+    let mut data = data.into_inner();
+    if let Err(e) = validate_content_warning(&data.content_warning) {
+        return e;
+    }
+    if let Err(e) = validate_attachments(&data.attachments, !data.image.is_empty()) {
+        return e;
+    }
+    if !data.image.is_empty() {
+        match resolve_media(&data.image, &pool).await {
+            Ok(id) => data.image = id,
+            Err(e) => return e.into(),
+        }
+    }
+    for a in &mut data.attachments {
+        match resolve_media(&a.data, &pool).await {
+            Ok(id) => a.data = id,
+            Err(e) => return e.into(),
+        }
+    }
 
-    if database::edit_post(&post_id, &data, &pool).await.is_err() {
+    if database::edit_post(&post_id, &data, &date, &pool)
+        .await
+        .is_err()
+    {
         return Custom(Status::InternalServerError, "InternalServerError");
     }
     Custom(Status::Ok, "post edited")
 }
+
+/// How long after publishing a post can still be edited, configurable through
+/// `POST_EDIT_WINDOW_MINUTES`.
+fn edit_window_millis() -> i64 {
+    let minutes = dotenv::var("POST_EDIT_WINDOW_MINUTES")
+        .ok()
+        .and_then(|m| m.parse::<i64>().ok())
+        .unwrap_or(POST_EDIT_WINDOW_DEFAULT_MINUTES);
+    minutes * 60 * 1000
+}
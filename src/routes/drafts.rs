@@ -0,0 +1,260 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::{
+    http::{CookieJar, Status},
+    response::status::Custom,
+    serde::json::{self, Json},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::validate_jwt,
+    database::{self, Draft},
+};
+
+use super::{
+    types::DataResponse,
+    user::{post_media_urls, resolve_post_media, validate_post_data, PostData},
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResponseDraft {
+    #[serde(rename = "draftId")]
+    pub draft_id: i32,
+    #[serde(rename = "unixTime")]
+    pub unix_time: String,
+    pub post: PostData,
+}
+
+fn make_response_draft(draft: Draft) -> Option<ResponseDraft> {
+    let post = json::from_str::<PostData>(&draft.post_data).ok()?;
+    Some(ResponseDraft {
+        draft_id: draft.draft_id,
+        unix_time: draft.unix_time.to_string(),
+        post: post_media_urls(post),
+    })
+}
+
+#[post(
+    "/user/create-draft",
+    format = "application/json",
+    data = "<post_data>"
+)]
+pub async fn create_draft(
+    post_data: Json<PostData>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<ResponseDraft, &'static str>> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+
+    let mut data = post_data.into_inner();
+    if let Err(Custom(status, message)) = validate_post_data(&data) {
+        return DataResponse {
+            status,
+            data: Json(Err(message)),
+        };
+    }
+
+    let pool = database::connect_db().await;
+    if let Err(Custom(status, message)) = resolve_post_media(&mut data, &pool).await {
+        return DataResponse {
+            status,
+            data: Json(Err(message)),
+        };
+    }
+
+    let Some(draft) = database::create_draft(&s.id, &data, &date, &pool)
+        .await
+        .ok()
+        .and_then(make_response_draft)
+    else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Created,
+        data: Json(Ok(draft)),
+    }
+}
+
+#[patch(
+    "/user/edit-draft/<draft_id>",
+    format = "application/json",
+    data = "<post_data>"
+)]
+pub async fn edit_draft(
+    draft_id: i32,
+    post_data: Json<PostData>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<ResponseDraft, &'static str>> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+
+    let mut data = post_data.into_inner();
+    if let Err(Custom(status, message)) = validate_post_data(&data) {
+        return DataResponse {
+            status,
+            data: Json(Err(message)),
+        };
+    }
+
+    let pool = database::connect_db().await;
+    if let Err(Custom(status, message)) = resolve_post_media(&mut data, &pool).await {
+        return DataResponse {
+            status,
+            data: Json(Err(message)),
+        };
+    }
+
+    let draft = match database::update_draft(&draft_id, &s.id, &data, &date, &pool).await {
+        Ok(Some(d)) => d,
+        Ok(None) => {
+            return DataResponse {
+                status: Status::NotFound,
+                data: Json(Err("Draft not found")),
+            };
+        }
+        Err(..) => {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        }
+    };
+    let Some(draft) = make_response_draft(draft) else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(draft)),
+    }
+}
+
+#[get("/user/drafts", format = "application/json")]
+pub async fn fetch_drafts(
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<ResponseDraft>, &'static str>> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let pool = database::connect_db().await;
+
+    let Ok(drafts) = database::get_drafts(&s.id, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(drafts
+            .into_iter()
+            .filter_map(make_response_draft)
+            .collect())),
+    }
+}
+
+#[delete("/user/delete-draft/<draft_id>")]
+pub async fn delete_draft(draft_id: i32, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    match database::delete_draft(&draft_id, &s.id, &pool).await {
+        Ok(true) => Custom(Status::NoContent, "Draft deleted"),
+        Ok(false) => Custom(Status::NotFound, "Draft not found"),
+        Err(..) => Custom(Status::InternalServerError, "InternalServerError"),
+    }
+}
+
+#[post("/user/publish-draft/<draft_id>")]
+pub async fn publish_draft(draft_id: i32, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    let draft = match database::get_draft(&draft_id, &s.id, &pool).await {
+        Ok(Some(d)) => d,
+        Ok(None) => return Custom(Status::NotFound, "Draft not found"),
+        Err(..) => return Custom(Status::InternalServerError, "InternalServerError"),
+    };
+    let Ok(data) = json::from_str::<PostData>(&draft.post_data) else {
+        return Custom(Status::InternalServerError, "InternalServerError");
+    };
+    if let Err(e) = validate_post_data(&data) {
+        return e;
+    }
+    if data.publish_at.is_some_and(|publish_at| publish_at <= date) {
+        return Custom(Status::BadRequest, "publishAt must be in the future");
+    }
+
+    match database::publish_draft(&draft_id, &s.id, &date, &pool).await {
+        Ok(true) if data.publish_at.is_some() => Custom(Status::Created, "Post scheduled"),
+        Ok(true) => Custom(Status::Ok, "Ok"),
+        Ok(false) => Custom(Status::NotFound, "Draft not found"),
+        Err(..) => Custom(Status::InternalServerError, "InternalServerError"),
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::{
+    http::{CookieJar, Status},
+    response::status::Custom,
+    serde::json::Json,
+};
+
+use crate::{
+    auth::validate_jwt,
+    database::{self, FollowData},
+    media::media_url,
+};
+
+use super::{
+    blocks::get_user_id,
+    types::{DataResponse, UpdatedFollowData},
+};
+
+fn make_follow_data(users: Vec<FollowData>) -> Vec<UpdatedFollowData> {
+    users
+        .into_iter()
+        .map(|u| UpdatedFollowData {
+            user_at: u.userat,
+            username: u.username,
+            icon: media_url(u.icon_id),
+        })
+        .collect()
+}
+
+/// Requests other users made to follow the caller.
+#[get("/user/follow-requests/incoming", format = "application/json")]
+pub async fn fetch_incoming_follow_requests(
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<UpdatedFollowData>, &'static str>> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let pool = database::connect_db().await;
+
+    let Ok(requests) = database::get_incoming_follow_requests(&s.id, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(make_follow_data(requests))),
+    }
+}
+
+/// Requests the caller made that are still pending.
+#[get("/user/follow-requests/outgoing", format = "application/json")]
+pub async fn fetch_outgoing_follow_requests(
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<UpdatedFollowData>, &'static str>> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let pool = database::connect_db().await;
+
+    let Ok(requests) = database::get_outgoing_follow_requests(&s.id, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(make_follow_data(requests))),
+    }
+}
+
+#[post("/user/follow-requests/<user_at>/approve")]
+pub async fn approve_follow_request(
+    user_at: &str,
+    cookies: &CookieJar<'_>,
+) -> Custom<&'static str> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    let requester_id = match get_user_id(user_at, &pool).await {
+        Ok(id) => id,
+        Err(e) => return e,
+    };
+
+    match database::approve_follow_request(&s.id, &requester_id, &date, &pool).await {
+        Ok(true) => Custom(Status::Ok, "Follow request approved"),
+        Ok(false) => Custom(Status::NotFound, "Follow request not found"),
+        Err(..) => Custom(Status::InternalServerError, "InternalServerError"),
+    }
+}
+
+#[delete("/user/follow-requests/<user_at>")]
+pub async fn reject_follow_request(user_at: &str, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    let requester_id = match get_user_id(user_at, &pool).await {
+        Ok(id) => id,
+        Err(e) => return e,
+    };
+
+    match database::remove_follow_request(&s.id, &requester_id, &pool).await {
+        Ok(true) => Custom(Status::Ok, "Follow request rejected"),
+        Ok(false) => Custom(Status::NotFound, "Follow request not found"),
+        Err(..) => Custom(Status::InternalServerError, "InternalServerError"),
+    }
+}
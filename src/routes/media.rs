@@ -0,0 +1,287 @@
+use std::io::Cursor;
+
+use rocket::{
+    data::Capped,
+    form::Form,
+    fs::TempFile,
+    http::{ContentType, CookieJar, Header, Status},
+    request::{FromRequest, Outcome},
+    response::{status::Custom, Responder},
+    serde::json::Json,
+    Request, Response,
+};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    auth::validate_jwt,
+    database,
+    media::{is_media_id, media_store, media_url, save_upload, MediaType},
+};
+
+use super::types::DataResponse;
+
+/// Media never changes under the same id, so it can be cached for as long as
+/// browsers allow.
+const MEDIA_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+pub struct MediaFile {
+    media_id: String,
+    content_type: ContentType,
+    data: Vec<u8>,
+    /// The first and last byte sent and the size of the media, when only a
+    /// range of it is sent.
+    range: Option<(u64, u64, u64)>,
+}
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for MediaFile {
+    fn respond_to(self, _request: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        let mut response = Response::build();
+        if let Some((start, end, size)) = self.range {
+            response.status(Status::PartialContent).header(Header::new(
+                "Content-Range",
+                format!("bytes {start}-{end}/{size}"),
+            ));
+        }
+        response
+            .header(self.content_type)
+            .header(Header::new("Accept-Ranges", "bytes"))
+            .header(Header::new("Cache-Control", MEDIA_CACHE_CONTROL))
+            .header(Header::new("ETag", format!("\"{}\"", self.media_id)))
+            .sized_body(self.data.len(), Cursor::new(self.data))
+            .ok()
+    }
+}
+
+/// The `Range` header of the request, it can only be checked once the size
+/// of the media is known.
+pub struct RangeHeader(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeHeader {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RangeHeader(
+            request.headers().get_one("Range").map(String::from),
+        ))
+    }
+}
+
+/// Parses a single `bytes` range into the first and last byte. Anything else,
+/// like multiple ranges, is ignored and the whole media is served, as HTTP
+/// allows.
+fn parse_range(header: &str, size: u64) -> Result<Option<(u64, u64)>, Status> {
+    let Some((start, end)) = header
+        .strip_prefix("bytes=")
+        .filter(|spec| !spec.contains(','))
+        .and_then(|spec| spec.split_once('-'))
+    else {
+        return Ok(None);
+    };
+    let (start, end) = (start.trim(), end.trim());
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // the last `end` bytes
+        (Err(_), Ok(suffix)) if start.is_empty() && suffix > 0 => {
+            (size.saturating_sub(suffix), size.saturating_sub(1))
+        }
+        (Ok(start), Err(_)) if end.is_empty() => (start, size.saturating_sub(1)),
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(size.saturating_sub(1))),
+        (Err(_), Ok(0)) if start.is_empty() => return Err(Status::RangeNotSatisfiable),
+        _ => return Ok(None),
+    };
+    if size == 0 || range.0 >= size {
+        return Err(Status::RangeNotSatisfiable);
+    }
+    Ok(Some(range))
+}
+
+/// Supports range requests so videos can be seeked without downloading them
+/// first.
+#[get("/media/<media_id>")]
+pub async fn get_media(media_id: &str, range: RangeHeader) -> Result<MediaFile, Status> {
+    if !is_media_id(media_id) {
+        return Err(Status::NotFound);
+    }
+    let pool = database::connect_db().await;
+    serve_media(media_id, range, &pool).await
+}
+
+/// Smaller copies of images, `preview` and `thumbnail`, or `thumbnail` and
+/// `small` for icons. Images that are already small don't have every
+/// variant, the original is served instead.
+#[get("/media/<media_id>/<variant>")]
+pub async fn get_media_variant(
+    media_id: &str,
+    variant: &str,
+    range: RangeHeader,
+) -> Result<MediaFile, Status> {
+    if !is_media_id(media_id) {
+        return Err(Status::NotFound);
+    }
+    let pool = database::connect_db().await;
+
+    match database::get_media_variant(&pool, media_id, variant).await {
+        Ok(Some(variant_id)) => serve_media(&variant_id, range, &pool).await,
+        Ok(None) => serve_media(media_id, range, &pool).await,
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+async fn serve_media(
+    media_id: &str,
+    range: RangeHeader,
+    pool: &Pool<Postgres>,
+) -> Result<MediaFile, Status> {
+    let Ok(media) = database::get_media(pool, media_id).await else {
+        return Err(Status::InternalServerError);
+    };
+    let Some(media) = media else {
+        return Err(Status::NotFound);
+    };
+    let size = media.size as u64;
+    let range = match range.0 {
+        Some(header) => parse_range(&header, size)?,
+        None => None,
+    };
+
+    let data = match range {
+        Some((start, end)) => media_store().get_range(media_id, start, end).await,
+        None => media_store().get(media_id).await,
+    };
+    let data = match data {
+        Ok(Some(data)) => data,
+        Ok(None) => return Err(Status::NotFound),
+        Err(e) => {
+            error!("{e}");
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    Ok(MediaFile {
+        media_id: media_id.to_string(),
+        content_type: ContentType::parse_flexible(&media.mime_type).unwrap_or(ContentType::Binary),
+        data,
+        range: range.map(|(start, end)| (start, end, size)),
+    })
+}
+
+#[derive(FromForm)]
+pub struct MediaUpload<'r> {
+    /// Capped by the `file` limit in Rocket.toml, which has to be at least the
+    /// biggest of the per kind limits.
+    file: Capped<TempFile<'r>>,
+}
+
+#[derive(Serialize)]
+pub struct UploadedMedia {
+    #[serde(rename = "mediaId")]
+    media_id: String,
+    url: String,
+    #[serde(rename = "mimeType")]
+    mime_type: &'static str,
+    #[serde(rename = "mediaType")]
+    media_type: MediaType,
+}
+
+/// Streams the file to disk instead of sending it base64 encoded in JSON, the
+/// returned id can be used as the image, attachments or icon of posts,
+/// comments and profiles.
+#[post("/media/upload", format = "multipart/form-data", data = "<upload>")]
+pub async fn upload_media(
+    upload: Form<MediaUpload<'_>>,
+    cookies: &CookieJar<'_>,
+) -> Result<DataResponse<UploadedMedia>, Custom<&'static str>> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Err(Custom(Status::Forbidden, "forbidden"));
+    };
+    if validate_jwt(jwt.value()).await.is_err() {
+        return Err(Custom(Status::Forbidden, "forbidden"));
+    }
+    if !upload.file.is_complete() {
+        return Err(Custom(Status::PayloadTooLarge, "Media too large"));
+    }
+    let pool = database::connect_db().await;
+
+    let (media_id, mime_type) = save_upload(&upload.file, &pool).await?;
+    Ok(DataResponse {
+        status: Status::Created,
+        data: Json(UploadedMedia {
+            url: media_url(Some(media_id.clone())),
+            media_id,
+            mime_type,
+            media_type: MediaType::from_mime_type(mime_type),
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Ok(Some((0, 99))));
+        assert_eq!(parse_range("bytes= 10 - 19 ", 1000), Ok(Some((10, 19))));
+        // the end is clamped to the last byte
+        assert_eq!(parse_range("bytes=900-5000", 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_range("bytes=999-999", 1000), Ok(Some((999, 999))));
+    }
+
+    #[test]
+    fn parses_open_ended_ranges() {
+        assert_eq!(parse_range("bytes=0-", 1000), Ok(Some((0, 999))));
+        assert_eq!(parse_range("bytes=500-", 1000), Ok(Some((500, 999))));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-100", 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_range("bytes=-1", 1000), Ok(Some((999, 999))));
+        // longer than the media, the whole media
+        assert_eq!(parse_range("bytes=-5000", 1000), Ok(Some((0, 999))));
+        assert_eq!(
+            parse_range("bytes=-0", 1000),
+            Err(Status::RangeNotSatisfiable)
+        );
+    }
+
+    #[test]
+    fn refuses_ranges_past_the_end() {
+        assert_eq!(
+            parse_range("bytes=1000-", 1000),
+            Err(Status::RangeNotSatisfiable)
+        );
+        assert_eq!(
+            parse_range("bytes=1000-1999", 1000),
+            Err(Status::RangeNotSatisfiable)
+        );
+    }
+
+    #[test]
+    fn refuses_ranges_of_empty_media() {
+        for header in ["bytes=0-", "bytes=0-0", "bytes=-10"] {
+            assert_eq!(
+                parse_range(header, 0),
+                Err(Status::RangeNotSatisfiable),
+                "{header}"
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_other_ranges() {
+        for header in [
+            "bytes=0-9,20-29",
+            "bytes=0-9, 20-",
+            "bytes=10-5",
+            "bytes=-",
+            "bytes=a-b",
+            "bytes=0",
+            "items=0-9",
+            "",
+        ] {
+            assert_eq!(parse_range(header, 1000), Ok(None), "{header}");
+        }
+    }
+}
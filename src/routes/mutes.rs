@@ -0,0 +1,385 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::{
+    http::{CookieJar, Status},
+    response::status::Custom,
+    serde::json::Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    auth::validate_jwt,
+    database::{self, MutedWord},
+    media::media_url,
+};
+
+use super::{
+    blocks::get_user_id,
+    types::{DataResponse, UpdatedFollowData},
+};
+
+pub const MUTED_WORD_MAX_CHAR_LENGTH: usize = 100;
+pub const MAX_MUTED_WORDS: i64 = 100;
+
+/// Lowercases the phrase and collapses its whitespace, the way it's stored and
+/// matched.
+pub fn normalize_phrase(phrase: &str) -> String {
+    phrase
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Whether any of the muted phrases appears in the text as whole words, so
+/// muting "cat" hides "my cat" but not "category".
+pub fn contains_muted_word(text: &str, muted_words: &[String]) -> bool {
+    if muted_words.is_empty() {
+        return false;
+    }
+    let text = normalize_phrase(text);
+    muted_words.iter().any(|phrase| {
+        text.match_indices(phrase.as_str()).any(|(i, m)| {
+            let before = text[..i].chars().next_back();
+            let after = text[i + m.len()..].chars().next();
+            // only a boundary between two word characters splits a word
+            let joins = |outer: Option<char>, inner: Option<char>| {
+                outer.is_some_and(char::is_alphanumeric) && inner.is_some_and(char::is_alphanumeric)
+            };
+            !joins(before, m.chars().next()) && !joins(after, m.chars().next_back())
+        })
+    })
+}
+
+/// The phrases the viewer muted that haven't expired, none for anonymous
+/// viewers.
+pub async fn get_viewer_muted_words(
+    viewer_id: &Option<i32>,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<String>, ()> {
+    let Some(viewer_id) = viewer_id else {
+        return Ok(vec![]);
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+    let Ok(words) = database::get_muted_words(viewer_id, &now, pool).await else {
+        return Err(());
+    };
+    Ok(words.into_iter().map(|w| w.phrase).collect())
+}
+
+#[post("/user/mute/<user_at>")]
+pub async fn mute_user(user_at: &str, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    if s.user_at == user_at {
+        return Custom(Status::BadRequest, "You can't mute yourself");
+    }
+    let pool = database::connect_db().await;
+
+    let muted_id = match get_user_id(user_at, &pool).await {
+        Ok(id) => id,
+        Err(e) => return e,
+    };
+
+    if database::mute_user(&s.id, &muted_id, &date, &pool)
+        .await
+        .is_err()
+    {
+        return Custom(Status::InternalServerError, "InternalServerError");
+    }
+
+    Custom(Status::Ok, "User muted")
+}
+
+#[delete("/user/mute/<user_at>")]
+pub async fn unmute_user(user_at: &str, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    let muted_id = match get_user_id(user_at, &pool).await {
+        Ok(id) => id,
+        Err(e) => return e,
+    };
+
+    if database::unmute_user(&s.id, &muted_id, &pool)
+        .await
+        .is_err()
+    {
+        return Custom(Status::InternalServerError, "InternalServerError");
+    }
+
+    Custom(Status::Ok, "User unmuted")
+}
+
+#[get("/user/mutes", format = "application/json")]
+pub async fn fetch_mutes(
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<UpdatedFollowData>, &'static str>> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let pool = database::connect_db().await;
+
+    let Ok(muted) = database::get_muted_list(&s.id, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(muted
+            .into_iter()
+            .map(|m| UpdatedFollowData {
+                user_at: m.userat,
+                username: m.username,
+                icon: media_url(m.icon_id),
+            })
+            .collect())),
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MutedWordData {
+    pub phrase: String,
+    /// Muted forever when missing.
+    #[serde(rename = "expiresAt", default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResponseMutedWord {
+    #[serde(rename = "wordId")]
+    pub word_id: i32,
+    pub phrase: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<String>,
+}
+
+fn make_response_muted_word(word: MutedWord) -> ResponseMutedWord {
+    ResponseMutedWord {
+        word_id: word.word_id,
+        phrase: word.phrase,
+        expires_at: word.expires_at.map(|t| t.to_string()),
+    }
+}
+
+#[post("/user/muted-words", format = "application/json", data = "<word_data>")]
+pub async fn add_muted_word(
+    word_data: Json<MutedWordData>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<ResponseMutedWord, &'static str>> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+
+    let data = word_data.into_inner();
+    let phrase = normalize_phrase(&data.phrase);
+    if phrase.is_empty() {
+        return DataResponse {
+            status: Status::BadRequest,
+            data: Json(Err("Muted word was empty")),
+        };
+    }
+    if phrase.chars().count() > MUTED_WORD_MAX_CHAR_LENGTH {
+        return DataResponse {
+            status: Status::BadRequest,
+            data: Json(Err("Muted word too long")),
+        };
+    }
+    if data.expires_at.is_some_and(|t| t <= date) {
+        return DataResponse {
+            status: Status::BadRequest,
+            data: Json(Err("expiresAt must be in the future")),
+        };
+    }
+
+    let pool = database::connect_db().await;
+
+    match database::add_muted_word(
+        &s.id,
+        &phrase,
+        &data.expires_at,
+        &date,
+        &MAX_MUTED_WORDS,
+        &pool,
+    )
+    .await
+    {
+        Ok(Some(word)) => DataResponse {
+            status: Status::Created,
+            data: Json(Ok(make_response_muted_word(word))),
+        },
+        Ok(None) => DataResponse {
+            status: Status::BadRequest,
+            data: Json(Err("Too many muted words")),
+        },
+        Err(..) => DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        },
+    }
+}
+
+#[get("/user/muted-words", format = "application/json")]
+pub async fn fetch_muted_words(
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<ResponseMutedWord>, &'static str>> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let pool = database::connect_db().await;
+
+    let Ok(words) = database::get_muted_words(&s.id, &date, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(words
+            .into_iter()
+            .map(make_response_muted_word)
+            .collect())),
+    }
+}
+
+#[delete("/user/muted-words/<word_id>")]
+pub async fn delete_muted_word(word_id: i32, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    match database::delete_muted_word(&word_id, &s.id, &pool).await {
+        Ok(true) => Custom(Status::Ok, "Muted word removed"),
+        Ok(false) => Custom(Status::NotFound, "Muted word not found"),
+        Err(..) => Custom(Status::InternalServerError, "InternalServerError"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn muted(text: &str, phrases: &[&str]) -> bool {
+        let phrases: Vec<String> = phrases.iter().map(|p| normalize_phrase(p)).collect();
+        contains_muted_word(text, &phrases)
+    }
+
+    #[test]
+    fn normalizes_phrases() {
+        assert_eq!(normalize_phrase("  Hot \t DOG\n"), "hot dog");
+        assert_eq!(normalize_phrase("ÉCOLE  Ärger"), "école ärger");
+        assert_eq!(normalize_phrase(" \n "), "");
+    }
+
+    #[test]
+    fn matches_whole_words() {
+        assert!(muted("my cat", &["cat"]));
+        assert!(muted("Cat pictures", &["cat"]));
+        assert!(muted("CAT", &["cat"]));
+        assert!(!muted("category", &["cat"]));
+        assert!(!muted("concat", &["cat"]));
+        assert!(!muted("cats", &["cat"]));
+        // the second occurrence is a whole word
+        assert!(muted("category: cat", &["cat"]));
+        assert!(!muted("anything", &[]));
+    }
+
+    #[test]
+    fn matches_next_to_punctuation() {
+        assert!(muted("cat!", &["cat"]));
+        assert!(muted("(cat)", &["cat"]));
+        assert!(muted("#cat", &["cat"]));
+        assert!(muted("the cat's toy", &["cat"]));
+        assert!(muted("dog,cat,bird", &["cat"]));
+        // phrases ending in punctuation match before letters too
+        assert!(muted("c++rocks", &["c++"]));
+    }
+
+    #[test]
+    fn matches_phrases() {
+        assert!(muted("a hot dog stand", &["hot dog"]));
+        assert!(muted("HOT\n  dog", &["hot dog"]));
+        assert!(!muted("hot dogs", &["hot dog"]));
+        assert!(!muted("shot dog", &["hot dog"]));
+        assert!(!muted("hot, dog", &["hot dog"]));
+    }
+
+    #[test]
+    fn matches_unicode_boundaries() {
+        assert!(muted("un café noir", &["café"]));
+        assert!(muted("CAFÉ", &["café"]));
+        assert!(!muted("cafés", &["café"]));
+        assert!(!muted("décafé", &["café"]));
+        assert!(muted("«café»", &["café"]));
+        assert!(!muted("Straße", &["stra"]));
+        assert!(muted("Привет, мир", &["мир"]));
+        assert!(!muted("мировой", &["мир"]));
+    }
+}
@@ -0,0 +1,136 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::{
+    http::{CookieJar, Status},
+    response::status::Custom,
+    serde::json::{self, Json},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{auth::validate_jwt, database};
+
+use super::{
+    types::DataResponse,
+    user::{post_media_urls, resolve_post_media, validate_post_data, PostData},
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResponseScheduledPost {
+    #[serde(rename = "scheduledId")]
+    pub scheduled_id: i32,
+    #[serde(rename = "publishAt")]
+    pub publish_at: String,
+    /// `None` when what was stored can't be read anymore, the post can only
+    /// be cancelled then.
+    pub post: Option<PostData>,
+    /// Publishing failed, the post is kept until it's edited or cancelled.
+    pub failed: bool,
+}
+
+#[get("/user/scheduled-posts", format = "application/json")]
+pub async fn fetch_scheduled_posts(
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<ResponseScheduledPost>, &'static str>> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let pool = database::connect_db().await;
+
+    let Ok(scheduled) = database::get_scheduled_posts(&s.id, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    let mut response: Vec<ResponseScheduledPost> = vec![];
+    for p in scheduled {
+        let post = json::from_str::<PostData>(&p.post_data).ok();
+        response.push(ResponseScheduledPost {
+            scheduled_id: p.scheduled_id,
+            publish_at: p.publish_at.to_string(),
+            failed: p.failed || post.is_none(),
+            post: post.map(post_media_urls),
+        });
+    }
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(response)),
+    }
+}
+
+#[patch(
+    "/user/scheduled-post/<scheduled_id>",
+    format = "application/json",
+    data = "<post_data>"
+)]
+pub async fn edit_scheduled_post(
+    scheduled_id: i32,
+    post_data: Json<PostData>,
+    cookies: &CookieJar<'_>,
+) -> Custom<&'static str> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+
+    let mut data = post_data.into_inner();
+    if let Err(e) = validate_post_data(&data) {
+        return e;
+    }
+    let Some(publish_at) = data.publish_at else {
+        return Custom(Status::BadRequest, "publishAt is required");
+    };
+    if publish_at <= date {
+        return Custom(Status::BadRequest, "publishAt must be in the future");
+    }
+
+    let pool = database::connect_db().await;
+    if let Err(e) = resolve_post_media(&mut data, &pool).await {
+        return e;
+    }
+
+    match database::update_scheduled_post(&scheduled_id, &s.id, &data, &publish_at, &pool).await {
+        Ok(true) => Custom(Status::Ok, "Scheduled post edited"),
+        Ok(false) => Custom(Status::NotFound, "Scheduled post not found"),
+        Err(..) => Custom(Status::InternalServerError, "InternalServerError"),
+    }
+}
+
+#[delete("/user/scheduled-post/<scheduled_id>")]
+pub async fn cancel_scheduled_post(
+    scheduled_id: i32,
+    cookies: &CookieJar<'_>,
+) -> Custom<&'static str> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    match database::cancel_scheduled_post(&scheduled_id, &s.id, &pool).await {
+        Ok(true) => Custom(Status::NoContent, "Scheduled post cancelled"),
+        Ok(false) => Custom(Status::NotFound, "Scheduled post not found"),
+        Err(..) => Custom(Status::InternalServerError, "InternalServerError"),
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::{
+    http::{CookieJar, Status},
+    response::status::Custom,
+    serde::json::Json,
+    State,
+};
+
+use crate::{
+    auth::validate_jwt,
+    database,
+    media::media_url,
+    suggestions::{SuggestionsCache, SUGGESTIONS_CACHED},
+};
+
+use super::{
+    blocks::get_user_id,
+    types::{DataResponse, UpdatedFollowData},
+};
+
+const SUGGESTIONS_DEFAULT_LIMIT: i64 = 3;
+
+/// Accounts the caller might want to follow, best match first.
+#[get("/user/suggestions?<limit>", format = "application/json")]
+pub async fn fetch_suggestions(
+    limit: Option<i64>,
+    cache: &State<SuggestionsCache>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<UpdatedFollowData>, &'static str>> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let limit = limit
+        .unwrap_or(SUGGESTIONS_DEFAULT_LIMIT)
+        .clamp(1, SUGGESTIONS_CACHED);
+
+    let pool = database::connect_db().await;
+
+    let Ok(user_ids) = cache.get(&s.id, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+    let Ok(users) = database::filter_suggested_users(&s.id, &user_ids, &limit, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(users
+            .into_iter()
+            .map(|u| UpdatedFollowData {
+                user_at: u.userat,
+                username: u.username,
+                icon: media_url(u.icon_id),
+            })
+            .collect())),
+    }
+}
+
+/// Stops suggesting the user to the caller.
+#[post("/user/suggestions/<user_at>/dismiss")]
+pub async fn dismiss_suggestion(user_at: &str, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    let dismissed_id = match get_user_id(user_at, &pool).await {
+        Ok(id) => id,
+        Err(e) => return e,
+    };
+
+    if database::dismiss_suggestion(&s.id, &dismissed_id, &date, &pool)
+        .await
+        .is_err()
+    {
+        return Custom(Status::InternalServerError, "InternalServerError");
+    }
+
+    Custom(Status::Ok, "Suggestion dismissed")
+}
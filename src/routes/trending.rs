@@ -0,0 +1,63 @@
+use rocket::{
+    http::{CookieJar, Status},
+    serde::json::Json,
+    State,
+};
+
+use crate::{
+    database,
+    trending::{TrendingCache, TrendingTag},
+};
+
+use super::{
+    types::DataResponse,
+    user::{get_viewer_id, make_response_post, ResponsePost},
+};
+
+#[get("/trending/posts", format = "application/json")]
+pub async fn trending_posts(
+    cache: &State<TrendingCache>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<ResponsePost>, &'static str>> {
+    let post_ids = cache.0.read().unwrap().post_ids.clone();
+
+    let pool = database::connect_db().await;
+    let viewer_id = get_viewer_id(cookies).await;
+
+    // posts deleted since the last recompute, or from someone the viewer
+    // blocked or who blocked them, are left out
+    let Ok(posts) = database::get_visible_posts_by_ids(&pool, &post_ids, &viewer_id).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    let mut response_posts: Vec<ResponsePost> = vec![];
+    for p in posts {
+        let Ok(response_post) = make_response_post(p, viewer_id, &pool).await else {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        };
+        response_posts.push(response_post);
+    }
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(response_posts)),
+    }
+}
+
+#[get("/trending/tags", format = "application/json")]
+pub async fn trending_tags(
+    cache: &State<TrendingCache>,
+) -> DataResponse<Result<Vec<TrendingTag>, &'static str>> {
+    let tags = cache.0.read().unwrap().tags.clone();
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(tags)),
+    }
+}
@@ -1,7 +1,10 @@
+use std::{fmt, str::FromStr};
+
 use rocket::{
+    form::{self, FromFormField, ValueField},
     http::{ContentType, Status},
     response::Responder,
-    serde::{json::Json, Deserialize, Serialize},
+    serde::{json::Json, Deserialize, Deserializer, Serialize, Serializer},
     Response,
 };
 
@@ -19,8 +22,31 @@ pub struct ProfileData {
     pub is_following: bool,
     #[serde(rename = "isHimself")]
     pub is_himself: bool,
+    /// Whether this user follows the viewer.
+    #[serde(rename = "followsYou")]
+    pub follows_you: bool,
+    /// How many of the people the viewer follows follow this user.
+    #[serde(rename = "followedByCount")]
+    pub followed_by_count: i64,
+    /// A few of them, most recent follow first.
+    #[serde(rename = "followedBy")]
+    pub followed_by: Vec<UpdatedFollowData>,
+    /// Whether the viewer blocked this user.
+    #[serde(rename = "isBlocked")]
+    pub is_blocked: bool,
+    /// Whether the viewer muted this user, only ever shown to the viewer.
+    #[serde(rename = "isMuted")]
+    pub is_muted: bool,
+    #[serde(rename = "isPrivate")]
+    pub is_private: bool,
+    /// Whether the viewer asked to follow this private account and is waiting
+    /// for an answer.
+    #[serde(rename = "followRequested")]
+    pub follow_requested: bool,
     pub bio: String,
     pub icon: String,
+    #[serde(rename = "pinnedPostId")]
+    pub pinned_post_id: Option<i32>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -64,6 +90,7 @@ pub struct UpdatedClientUser {
     pub followerscount: i32,
     pub bio: Option<String>,
     pub icon: String,
+    pub private: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -77,7 +104,8 @@ pub struct ClientUser {
     #[serde(rename = "followersCount")]
     pub followerscount: i32,
     pub bio: Option<String>,
-    pub icon: Option<Vec<u8>>,
+    pub icon_id: Option<String>,
+    pub private: bool,
 }
 
 pub struct DataResponse<T> {
@@ -94,6 +122,56 @@ impl<'r, 'o: 'r, T: Serialize> Responder<'r, 'o> for DataResponse<T> {
     }
 }
 
+/// Where a page of a list ordered by time then id, newest first, ends. Sent as
+/// `<time>_<id>`, the id keeps entries made in the same millisecond from being
+/// skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub time: i64,
+    pub id: i32,
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.time, self.id)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (time, id) = s.split_once('_').ok_or(())?;
+        Ok(Cursor {
+            time: time.parse().map_err(|_| ())?,
+            id: id.parse().map_err(|_| ())?,
+        })
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|_| rocket::serde::de::Error::custom("invalid cursor"))
+    }
+}
+
+impl<'v> FromFormField<'v> for Cursor {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        field
+            .value
+            .parse()
+            .map_err(|_| form::Error::validation("invalid cursor").into())
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct UpdatedFollowData {
     #[serde(rename = "userAt")]
@@ -102,3 +180,22 @@ pub struct UpdatedFollowData {
     pub username: String,
     pub icon: String,
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FollowListUser {
+    #[serde(rename = "userAt")]
+    pub user_at: String,
+    #[serde(rename = "userName")]
+    pub username: String,
+    pub icon: String,
+    /// Whether the viewer follows this user.
+    #[serde(rename = "isFollowing")]
+    pub is_following: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FollowListPage {
+    pub users: Vec<FollowListUser>,
+    #[serde(rename = "nextCursor")]
+    pub next_cursor: Option<Cursor>,
+}
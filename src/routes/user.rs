@@ -2,11 +2,14 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::auth::validate_jwt;
 use crate::auth::{create_jwt, hash::hash_str};
-use crate::database::{self, delete_user, user_has_credentials};
+use crate::database::{
+    self, delete_user, user_has_credentials, Attachment, LinkPreview, Poll, Post,
+};
 use crate::database::{
     connect_db, email_exists, get_email_from_id, make_jwt_claims, make_user, user::User,
     verify_password,
 };
+use crate::media::{media_url, resolve_media, MediaType};
 use crate::{validate_email, validate_minimal_user_credentials, validate_password, LoginData};
 use core::str;
 use rocket::http::{Cookie, SameSite};
@@ -17,8 +20,12 @@ use rocket::{
     serde::json::Json,
 };
 use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
 
-use super::types::DataResponse;
+use super::{
+    mutes::{contains_muted_word, get_viewer_muted_words},
+    types::{Cursor, DataResponse},
+};
 
 #[post("/user/log-out")]
 pub async fn logout(cookies: &CookieJar<'_>) -> Custom<&'static str> {
@@ -166,6 +173,197 @@ pub async fn delete(cookies: &CookieJar<'_>) -> Custom<&'static str> {
 pub struct PostData {
     pub text: Option<String>,
     pub image: Option<String>,
+    #[serde(rename = "publishAt", default, skip_serializing_if = "Option::is_none")]
+    pub publish_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll: Option<PollData>,
+    #[serde(default)]
+    pub visibility: Visibility,
+    #[serde(
+        rename = "contentWarning",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub content_warning: Option<String>,
+    #[serde(default)]
+    pub sensitive: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<AttachmentData>,
+}
+
+/// An image attached to a post or comment, attachments are shown in the order
+/// they're sent in.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AttachmentData {
+    /// A data URL, or the id of uploaded media.
+    #[serde(alias = "mediaId")]
+    pub data: String,
+    #[serde(rename = "altText", default)]
+    pub alt_text: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    Public,
+    Followers,
+    Mentioned,
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Followers => "followers",
+            Visibility::Mentioned => "mentioned",
+        }
+    }
+}
+
+/// How the viewer wants the media of posts flagged as `sensitive` delivered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SensitiveMedia {
+    /// The image is sent and the client blurs it until it's clicked.
+    #[default]
+    Blur,
+    /// The image isn't sent at all.
+    Hide,
+}
+
+impl SensitiveMedia {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SensitiveMedia::Blur => "blur",
+            SensitiveMedia::Hide => "hide",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "hide" => SensitiveMedia::Hide,
+            _ => SensitiveMedia::Blur,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PollData {
+    pub options: Vec<String>,
+    #[serde(rename = "closesAt")]
+    pub closes_at: i64,
+}
+
+pub const POST_MAX_CHAR_LENGTH: usize = 200;
+pub const CONTENT_WARNING_MAX_CHAR_LENGTH: usize = 100;
+/// Counting the legacy `image`.
+pub const MAX_ATTACHMENTS: usize = 4;
+pub const ATTACHMENT_ALT_TEXT_MAX_CHAR_LENGTH: usize = 1000;
+pub const POLL_MIN_OPTIONS: usize = 2;
+pub const POLL_MAX_OPTIONS: usize = 4;
+pub const POLL_OPTION_MAX_CHAR_LENGTH: usize = 25;
+pub const POLL_MAX_DURATION_MILLIS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+pub fn validate_post_data(data: &PostData) -> Result<(), Custom<&'static str>> {
+    if data.text.is_none()
+        && data.image.is_none()
+        && data.poll.is_none()
+        && data.attachments.is_empty()
+    {
+        return Err(Custom(Status::BadRequest, "Bad request, post was empty"));
+    }
+    if let Some(text) = &data.text {
+        if text.len() > POST_MAX_CHAR_LENGTH {
+            return Err(Custom(Status::BadRequest, "Text too long"));
+        }
+    }
+    validate_content_warning(&data.content_warning)?;
+    validate_attachments(&data.attachments, data.image.is_some())?;
+    if let Some(poll) = &data.poll {
+        validate_poll(poll, data.publish_at)?;
+    }
+    Ok(())
+}
+
+/// Comments take the same data as posts, without a poll, so one of the text,
+/// the image or attachments is required.
+fn validate_comment_data(data: &PostData) -> Result<(), Custom<&'static str>> {
+    if data.poll.is_some() {
+        return Err(Custom(Status::BadRequest, "Comments can't have polls"));
+    }
+    validate_post_data(data)
+}
+
+pub fn validate_content_warning(
+    content_warning: &Option<String>,
+) -> Result<(), Custom<&'static str>> {
+    if let Some(cw) = content_warning {
+        if cw.trim().is_empty() {
+            return Err(Custom(Status::BadRequest, "Content warning was empty"));
+        }
+        if cw.chars().count() > CONTENT_WARNING_MAX_CHAR_LENGTH {
+            return Err(Custom(Status::BadRequest, "Content warning too long"));
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_attachments(
+    attachments: &[AttachmentData],
+    has_image: bool,
+) -> Result<(), Custom<&'static str>> {
+    if attachments.len() + has_image as usize > MAX_ATTACHMENTS {
+        return Err(Custom(Status::BadRequest, "Too many attachments"));
+    }
+    for a in attachments {
+        if a.data.is_empty() {
+            return Err(Custom(Status::BadRequest, "Attachment was empty"));
+        }
+        if let Some(alt_text) = &a.alt_text {
+            if alt_text.chars().count() > ATTACHMENT_ALT_TEXT_MAX_CHAR_LENGTH {
+                return Err(Custom(Status::BadRequest, "Alt text too long"));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_poll(poll: &PollData, publish_at: Option<i64>) -> Result<(), Custom<&'static str>> {
+    if poll.options.len() < POLL_MIN_OPTIONS {
+        return Err(Custom(Status::BadRequest, "Poll needs at least 2 options"));
+    }
+    if poll.options.len() > POLL_MAX_OPTIONS {
+        return Err(Custom(
+            Status::BadRequest,
+            "Poll can't have more than 4 options",
+        ));
+    }
+    for option in &poll.options {
+        if option.trim().is_empty() {
+            return Err(Custom(Status::BadRequest, "Poll option was empty"));
+        }
+        if option.trim().chars().count() > POLL_OPTION_MAX_CHAR_LENGTH {
+            return Err(Custom(Status::BadRequest, "Poll option too long"));
+        }
+    }
+
+    let opens_at = publish_at.unwrap_or(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("We're in 1969??")
+            .as_millis() as i64,
+    );
+    if poll.closes_at <= opens_at {
+        return Err(Custom(
+            Status::BadRequest,
+            "Poll must close after it's published",
+        ));
+    }
+    if poll.closes_at - opens_at > POLL_MAX_DURATION_MILLIS {
+        return Err(Custom(Status::BadRequest, "Poll can't stay open that long"));
+    }
+    Ok(())
 }
 
 #[post(
@@ -183,14 +381,10 @@ pub async fn publish_post(
         .expect("We're in 1969??")
         .as_millis() as i64;
 
-    let data = post_data.into_inner();
-    const POST_MAX_CHAR_LENGTH: usize = 200;
+    let mut data = post_data.into_inner();
     let cookie = cookies.get_private("auth_key");
-    if data.text.is_none() && data.image.is_none() {
-        return Custom(Status::BadRequest, "Bad request, post was empty");
-    }
-    if data.text.is_some() && data.text.as_ref().unwrap().len() > POST_MAX_CHAR_LENGTH {
-        return Custom(Status::BadRequest, "Text too long");
+    if let Err(e) = validate_post_data(&data) {
+        return e;
     }
     if cookie.is_none() {
         return Custom(Status::Forbidden, "Forbidden");
@@ -201,12 +395,24 @@ pub async fn publish_post(
     };
 
     let pool = database::connect_db().await;
+    if let Err(e) = resolve_post_media(&mut data, &pool).await {
+        return e;
+    }
 
-    let text = data.text.unwrap_or(String::from(""));
-    if database::post(&s.id, &text, &data.image, &date, &pool)
-        .await
-        .is_err()
-    {
+    if let Some(publish_at) = data.publish_at {
+        if publish_at <= date {
+            return Custom(Status::BadRequest, "publishAt must be in the future");
+        }
+        if database::schedule_post(&s.id, &data, &publish_at, &pool)
+            .await
+            .is_err()
+        {
+            return Custom(Status::InternalServerError, "InternalServerError");
+        }
+        return Custom(Status::Created, "Post scheduled");
+    }
+
+    if database::post(&s.id, &data, &date, &pool).await.is_err() {
         return Custom(Status::InternalServerError, "InternalServerError");
     }
 
@@ -234,6 +440,61 @@ pub struct ResponseComment {
     pub post_id: i32,
     #[serde(rename = "hasThisUserLiked")]
     pub has_this_user_liked: bool,
+    pub attachments: Vec<ResponseAttachment>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResponseAttachment {
+    pub url: String,
+    #[serde(rename = "altText")]
+    pub alt_text: Option<String>,
+    pub order: i32,
+    pub width: i32,
+    pub height: i32,
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+    /// Smaller copies, the same as `url` when the image is already small.
+    #[serde(rename = "previewUrl")]
+    pub preview_url: String,
+    #[serde(rename = "thumbnailUrl")]
+    pub thumbnail_url: String,
+    pub blurhash: Option<String>,
+    #[serde(rename = "mediaType")]
+    pub media_type: MediaType,
+    /// First frame of GIFs and videos.
+    #[serde(rename = "posterUrl")]
+    pub poster_url: Option<String>,
+    /// Of GIFs and videos, in milliseconds.
+    pub duration: Option<i32>,
+}
+
+pub fn make_response_attachments(attachments: Vec<Attachment>) -> Vec<ResponseAttachment> {
+    attachments
+        .into_iter()
+        .map(|a| ResponseAttachment {
+            // videos fall back to their poster, never to the video itself
+            preview_url: media_url(
+                a.preview_id
+                    .or(a.poster_id.clone())
+                    .or(Some(a.media_id.clone())),
+            ),
+            thumbnail_url: media_url(
+                a.thumbnail_id
+                    .or(a.poster_id.clone())
+                    .or(Some(a.media_id.clone())),
+            ),
+            blurhash: a.blurhash,
+            media_type: MediaType::from_mime_type(&a.mime_type),
+            poster_url: a.poster_id.map(|id| media_url(Some(id))),
+            duration: a.duration_ms,
+            url: media_url(Some(a.media_id)),
+            alt_text: a.alt_text,
+            order: a.position,
+            width: a.width,
+            height: a.height,
+            mime_type: a.mime_type,
+        })
+        .collect()
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -259,6 +520,195 @@ pub struct ResponsePost {
     pub has_this_user_liked: bool,
     #[serde(rename = "edited")]
     pub edited: bool,
+    #[serde(rename = "editedAt")]
+    pub edited_at: Option<String>,
+    pub bookmarked: bool,
+    pub poll: Option<ResponsePoll>,
+    pub pinned: bool,
+    pub visibility: String,
+    #[serde(rename = "linkPreview")]
+    pub link_preview: Option<LinkPreview>,
+    #[serde(rename = "contentWarning")]
+    pub content_warning: Option<String>,
+    pub sensitive: bool,
+    /// Set when the post is sensitive and has media, following the viewer's
+    /// preference, the image and attachments are left empty when it's `hide`.
+    #[serde(rename = "sensitiveMedia")]
+    pub sensitive_media: Option<SensitiveMedia>,
+    pub attachments: Vec<ResponseAttachment>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResponsePollOption {
+    pub text: String,
+    /// Hidden until the viewer voted or the poll closed.
+    pub votes: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResponsePoll {
+    pub options: Vec<ResponsePollOption>,
+    #[serde(rename = "closesAt")]
+    pub closes_at: String,
+    pub closed: bool,
+    #[serde(rename = "hasVoted")]
+    pub has_voted: bool,
+    #[serde(rename = "votedOption")]
+    pub voted_option: Option<i32>,
+    #[serde(rename = "totalVotes")]
+    pub total_votes: Option<i64>,
+}
+
+pub fn make_response_poll(poll: Poll) -> ResponsePoll {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+    let closed = poll.closes_at <= now;
+    let has_voted = poll.voted_option.is_some();
+    let show_results = closed || has_voted;
+    let total_votes: i64 = poll.options.iter().map(|o| o.votes).sum();
+
+    ResponsePoll {
+        options: poll
+            .options
+            .into_iter()
+            .map(|o| ResponsePollOption {
+                text: o.text,
+                votes: show_results.then_some(o.votes),
+            })
+            .collect(),
+        closes_at: poll.closes_at.to_string(),
+        closed,
+        has_voted,
+        voted_option: poll.voted_option,
+        total_votes: show_results.then_some(total_votes),
+    }
+}
+
+/// Turns the media ids stored in the post back into URLs for the client.
+pub fn post_media_urls(mut data: PostData) -> PostData {
+    data.image = data.image.map(|id| media_url(Some(id)));
+    for a in &mut data.attachments {
+        a.data = media_url(Some(a.data.clone()));
+    }
+    data
+}
+
+/// Replaces the image and attachments the client sent with media ids, see
+/// [`resolve_media`].
+pub async fn resolve_post_media(
+    data: &mut PostData,
+    pool: &Pool<Postgres>,
+) -> Result<(), Custom<&'static str>> {
+    data.image = match data.image.as_deref() {
+        None | Some("") => None,
+        Some(image) => Some(resolve_media(image, pool).await?),
+    };
+    for a in &mut data.attachments {
+        a.data = resolve_media(&a.data, pool).await?;
+    }
+    Ok(())
+}
+
+/// Leaves out the posts containing one of the viewer's muted phrases in their
+/// text or content warning, the viewer's own posts are always kept.
+pub fn filter_muted_posts(
+    posts: Vec<Post>,
+    viewer_id: &Option<i32>,
+    muted_words: &[String],
+) -> Vec<Post> {
+    posts
+        .into_iter()
+        .filter(|p| {
+            Some(p.owner_id) == *viewer_id
+                || !(contains_muted_word(p.text.as_deref().unwrap_or_default(), muted_words)
+                    || contains_muted_word(
+                        p.content_warning.as_deref().unwrap_or_default(),
+                        muted_words,
+                    ))
+        })
+        .collect()
+}
+
+/// Returns the id of the user who made the request, if they're logged in.
+pub async fn get_viewer_id(cookies: &CookieJar<'_>) -> Option<i32> {
+    let jwt = cookies.get_private("auth_key")?;
+    validate_jwt(jwt.value()).await.ok().map(|s| s.id)
+}
+
+/// Builds the client-facing post, `viewer_id` is used for the per-user flags.
+pub async fn make_response_post(
+    p: Post,
+    viewer_id: Option<i32>,
+    pool: &Pool<Postgres>,
+) -> Result<ResponsePost, ()> {
+    let Ok(email) = get_email_from_id(&p.owner_id, pool).await else {
+        return Err(());
+    };
+    let owner_data = database::get_client_data(&email, pool).await?;
+
+    let (has_this_user_liked, bookmarked) = match viewer_id {
+        Some(id) => {
+            let Ok(liked) = database::has_liked_post(pool, &p.post_id, &id).await else {
+                return Err(());
+            };
+            let Ok(bookmarked) = database::is_bookmarked(pool, &p.post_id, &id).await else {
+                return Err(());
+            };
+            (liked, bookmarked)
+        }
+        None => (false, false),
+    };
+    let Ok(poll) = database::get_poll(pool, &p.post_id, &viewer_id).await else {
+        return Err(());
+    };
+    let text = p.text.unwrap_or_default();
+    let Ok(link_preview) = database::get_link_preview(pool, &text).await else {
+        return Err(());
+    };
+    let Ok(attachments) = database::get_post_attachments(pool, &p.post_id).await else {
+        return Err(());
+    };
+    let mut attachments = make_response_attachments(attachments);
+    let mut image = media_url(p.image_id);
+    let sensitive_media = if p.sensitive && (!image.is_empty() || !attachments.is_empty()) {
+        let Ok(preference) = database::get_sensitive_media(pool, &viewer_id).await else {
+            return Err(());
+        };
+        if preference == SensitiveMedia::Hide {
+            image = String::new();
+            attachments.clear();
+        }
+        Some(preference)
+    } else {
+        None
+    };
+
+    Ok(ResponsePost {
+        edited: p.edited,
+        edited_at: p.edited_at.map(|t| t.to_string()),
+        has_this_user_liked,
+        bookmarked,
+        poll: poll.map(make_response_poll),
+        pinned: false,
+        visibility: p.visibility,
+        link_preview,
+        content_warning: p.content_warning,
+        sensitive: p.sensitive,
+        sensitive_media,
+        attachments,
+        owner_id: p.owner_id,
+        post_id: p.post_id,
+        unix_time: p.unix_time.to_string(),
+        user_at: owner_data.userat,
+        username: owner_data.username,
+        likes_count: p.likescount,
+        comments_count: p.commentscount,
+        icon: media_url(owner_data.icon_id),
+        text,
+        image,
+    })
 }
 
 #[get("/user/fetch-posts", format = "application/json")]
@@ -267,7 +717,9 @@ pub async fn fetch_posts(
 ) -> DataResponse<Result<Vec<ResponsePost>, &'static str>> {
     let pool = database::connect_db().await;
 
-    let posts = match database::get_posts(&pool).await {
+    let viewer_id = get_viewer_id(cookies).await;
+
+    let posts = match database::get_posts(&pool, &viewer_id).await {
         Ok(p) => p,
         Err(..) => {
             return DataResponse {
@@ -276,79 +728,23 @@ pub async fn fetch_posts(
             };
         }
     };
-    //let Ok(posts) = database::get_posts(&pool).await else {
-    //return DataResponse {
-    //    status: Status::InternalServerError,
-    //    data: Json(Err("InternalServerError")),
-    //};
-    //};
-    let mut response_posts: Vec<ResponsePost> = vec![];
-
-    let mut owner_id: Option<i32> = None;
-
-    if let Some(jwt) = cookies.get_private("auth_key") {
-        if let Ok(s) = validate_jwt(jwt.value()).await {
-            owner_id = Some(s.id);
+    let Ok(muted_words) = get_viewer_muted_words(&viewer_id, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
         };
     };
+    let posts = filter_muted_posts(posts, &viewer_id, &muted_words);
+    let mut response_posts: Vec<ResponsePost> = vec![];
 
     for p in posts {
-        let email = match database::get_email_from_id(&p.owner_id, &pool).await {
-            Ok(e) => e,
-            Err(..) => {
-                return DataResponse {
-                    status: Status::InternalServerError,
-                    data: Json(Err("InternalServerError")),
-                };
-            }
-        };
-        let Ok(owner_data) = database::get_client_data(&email, &pool).await else {
+        let Ok(response_post) = make_response_post(p, viewer_id, &pool).await else {
             return DataResponse {
                 status: Status::InternalServerError,
                 data: Json(Err("InternalServerError")),
             };
         };
-
-        let has_this_user_liked = if owner_id.is_none() {
-            false
-        } else {
-            let Ok(c) = database::likes_list_contains(&pool, &p.post_id, &owner_id.unwrap()).await
-            else {
-                return DataResponse {
-                    status: Status::InternalServerError,
-                    data: Json(Err("InternalServerError")),
-                };
-            };
-            c
-        };
-        response_posts.push(ResponsePost {
-            edited: p.edited,
-            has_this_user_liked,
-            owner_id: p.owner_id,
-            post_id: p.post_id,
-            unix_time: p.unix_time.to_string(),
-            user_at: owner_data.userat,
-            username: owner_data.username,
-            likes_count: p.likescount,
-            comments_count: p.commentscount,
-            icon: if owner_data.icon.is_some() {
-                let byte_array = owner_data.icon.unwrap_or(vec![]);
-                str::from_utf8(&byte_array).unwrap_or("").to_string()
-            } else {
-                String::from("")
-            },
-            text: if p.text.is_some() {
-                p.text.unwrap()
-            } else {
-                String::from("")
-            },
-            image: if p.image.is_some() {
-                let byte_array = p.image.unwrap_or(vec![]);
-                str::from_utf8(&byte_array).unwrap_or("").to_string()
-            } else {
-                String::from("")
-            },
-        });
+        response_posts.push(response_post);
     }
 
     DataResponse {
@@ -363,6 +759,24 @@ pub async fn fetch_post(
     cookies: &CookieJar<'_>,
 ) -> DataResponse<Result<ResponsePost, &'static str>> {
     let pool = database::connect_db().await;
+    let viewer_id = get_viewer_id(cookies).await;
+
+    match database::can_view_post(&pool, &post_id, &viewer_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return DataResponse {
+                status: Status::NotFound,
+                data: Json(Err("Not found")),
+            };
+        }
+        Err(..) => {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        }
+    }
+
     let post = if let Ok(p) = database::get_post_by_id(&pool, &post_id).await {
         p
     } else {
@@ -372,68 +786,13 @@ pub async fn fetch_post(
         };
     };
 
-    let Ok(email) = get_email_from_id(&post.owner_id, &pool).await else {
-        return DataResponse {
-            status: Status::InternalServerError,
-            data: Json(Err("InternalServerError")),
-        };
-    };
-    let Ok(owner_data) = database::get_client_data(&email, &pool).await else {
+    let Ok(response_post) = make_response_post(post, viewer_id, &pool).await else {
         return DataResponse {
             status: Status::InternalServerError,
             data: Json(Err("InternalServerError")),
         };
     };
 
-    let mut owner_id: Option<i32> = None;
-
-    if let Some(jwt) = cookies.get_private("auth_key") {
-        if let Ok(s) = validate_jwt(jwt.value()).await {
-            owner_id = Some(s.id);
-        };
-    };
-
-    let has_this_user_liked = if owner_id.is_none() {
-        false
-    } else {
-        let Ok(c) = database::likes_list_contains(&pool, &post.post_id, &owner_id.unwrap()).await
-        else {
-            return DataResponse {
-                status: Status::InternalServerError,
-                data: Json(Err("InternalServerError")),
-            };
-        };
-        c
-    };
-    let response_post = ResponsePost {
-        edited: post.edited,
-        has_this_user_liked,
-        owner_id: post.owner_id,
-        post_id: post.post_id,
-        unix_time: post.unix_time.to_string(),
-        user_at: owner_data.userat,
-        username: owner_data.username,
-        likes_count: post.likescount,
-        comments_count: post.commentscount,
-        icon: if owner_data.icon.is_some() {
-            let byte_array = owner_data.icon.unwrap_or(vec![]);
-            str::from_utf8(&byte_array).unwrap_or("").to_string()
-        } else {
-            String::from("")
-        },
-        text: if post.text.is_some() {
-            post.text.unwrap()
-        } else {
-            String::from("")
-        },
-        image: if post.image.is_some() {
-            let byte_array = post.image.unwrap_or(vec![]);
-            str::from_utf8(&byte_array).unwrap_or("").to_string()
-        } else {
-            String::from("")
-        },
-    };
-
     DataResponse {
         status: Status::Ok,
         data: Json(Ok(response_post)),
@@ -461,7 +820,22 @@ pub async fn fetch_user_posts(
             data: Json(Err("InternalServerError")),
         };
     };
-    let Ok(posts) = database::get_user_posts(&pool, &owner_id).await else {
+    let viewer_id = get_viewer_id(cookies).await;
+
+    let Ok(posts) = database::get_user_posts(&pool, &owner_id, &viewer_id).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+    let Ok(muted_words) = get_viewer_muted_words(&viewer_id, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+    let posts = filter_muted_posts(posts, &viewer_id, &muted_words);
+    let Ok(pinned_post_id) = database::get_pinned_post_id(&owner_id, &pool).await else {
         return DataResponse {
             status: Status::InternalServerError,
             data: Json(Err("InternalServerError")),
@@ -470,143 +844,476 @@ pub async fn fetch_user_posts(
 
     let mut response_posts: Vec<ResponsePost> = vec![];
 
-    let mut owner_id: Option<i32> = None;
-
-    if let Some(jwt) = cookies.get_private("auth_key") {
-        if let Ok(s) = validate_jwt(jwt.value()).await {
-            owner_id = Some(s.id);
-        };
-    };
+    let (pinned, posts): (Vec<Post>, Vec<Post>) = posts
+        .into_iter()
+        .partition(|p| Some(p.post_id) == pinned_post_id);
 
-    for p in posts {
-        let Ok(email) = get_email_from_id(&p.owner_id, &pool).await else {
+    for p in pinned {
+        let Ok(mut response_post) = make_response_post(p, viewer_id, &pool).await else {
             return DataResponse {
                 status: Status::InternalServerError,
                 data: Json(Err("InternalServerError")),
             };
         };
-        let Ok(owner_data) = database::get_client_data(&email, &pool).await else {
+        response_post.pinned = true;
+        response_posts.push(response_post);
+    }
+
+    for p in posts {
+        let Ok(response_post) = make_response_post(p, viewer_id, &pool).await else {
             return DataResponse {
                 status: Status::InternalServerError,
                 data: Json(Err("InternalServerError")),
             };
         };
+        response_posts.push(response_post);
+    }
 
-        let has_this_user_liked = if owner_id.is_none() {
-            false
-        } else {
-            let Ok(c) = database::likes_list_contains(&pool, &p.post_id, &owner_id.unwrap()).await
-            else {
-                return DataResponse {
-                    status: Status::InternalServerError,
-                    data: Json(Err("InternalServerError")),
-                };
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(response_posts)),
+    }
+}
+
+#[patch("/user/pin-post/<post_id>")]
+pub async fn pin_post(post_id: i32, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    match database::pin_post(&s.id, &post_id, &pool).await {
+        Ok(true) => Custom(Status::Ok, "Post pinned"),
+        Ok(false) => Custom(Status::NotFound, "Post not found"),
+        Err(..) => Custom(Status::InternalServerError, "InternalServerError"),
+    }
+}
+
+#[delete("/user/pin-post")]
+pub async fn unpin_post(cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    if database::unpin_post(&s.id, &pool).await.is_err() {
+        return Custom(Status::InternalServerError, "InternalServerError");
+    }
+    Custom(Status::Ok, "Post unpinned")
+}
+
+#[post("/user/bookmark/<post_id>")]
+pub async fn bookmark(post_id: i32, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    match database::can_view_post(&pool, &post_id, &Some(s.id)).await {
+        Ok(true) => {}
+        Ok(false) => return Custom(Status::NotFound, "Post not found"),
+        Err(..) => return Custom(Status::InternalServerError, "InternalServerError"),
+    }
+
+    if database::bookmark(&pool, &s.id, &post_id, &date)
+        .await
+        .is_err()
+    {
+        return Custom(Status::InternalServerError, "InternalServerError");
+    }
+
+    Custom(Status::Ok, "Ok")
+}
+
+#[delete("/user/bookmark/<post_id>")]
+pub async fn remove_bookmark(post_id: i32, cookies: &CookieJar<'_>) -> Custom<&'static str> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return Custom(Status::Forbidden, "Forbidden");
+    };
+    let pool = database::connect_db().await;
+
+    if database::remove_bookmark(&pool, &s.id, &post_id)
+        .await
+        .is_err()
+    {
+        return Custom(Status::InternalServerError, "InternalServerError");
+    }
+
+    Custom(Status::Ok, "Ok")
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BookmarksPage {
+    pub posts: Vec<ResponsePost>,
+    #[serde(rename = "nextCursor")]
+    pub next_cursor: Option<Cursor>,
+}
+
+const BOOKMARKS_DEFAULT_LIMIT: i64 = 20;
+const BOOKMARKS_MAX_LIMIT: i64 = 50;
+
+#[get("/user/bookmarks?<limit>&<cursor>", format = "application/json")]
+pub async fn fetch_bookmarks(
+    limit: Option<i64>,
+    cursor: Option<Cursor>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<BookmarksPage, &'static str>> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let limit = limit
+        .unwrap_or(BOOKMARKS_DEFAULT_LIMIT)
+        .clamp(1, BOOKMARKS_MAX_LIMIT);
+
+    let pool = database::connect_db().await;
+
+    let Ok(bookmarks) =
+        database::get_bookmarked_posts(&pool, &s.id, &limit, &cursor.map(|c| (c.time, c.id))).await
+    else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    let next_cursor = if bookmarks.len() as i64 == limit {
+        bookmarks.last().map(|(bookmarked_at, p)| Cursor {
+            time: *bookmarked_at,
+            id: p.post_id,
+        })
+    } else {
+        None
+    };
+
+    let mut posts: Vec<ResponsePost> = vec![];
+    for (_, p) in bookmarks {
+        let Ok(response_post) = make_response_post(p, Some(s.id), &pool).await else {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
             };
-            c
         };
-        response_posts.push(ResponsePost {
-            edited: p.edited,
-            has_this_user_liked,
-            owner_id: p.owner_id,
-            post_id: p.post_id,
-            unix_time: p.unix_time.to_string(),
-            user_at: owner_data.userat,
-            username: owner_data.username,
-            likes_count: p.likescount,
-            comments_count: p.commentscount,
-            icon: if owner_data.icon.is_some() {
-                let byte_array = owner_data.icon.unwrap_or(vec![]);
-                str::from_utf8(&byte_array).unwrap_or("").to_string()
-            } else {
-                String::from("")
-            },
-            text: if p.text.is_some() {
-                p.text.unwrap()
-            } else {
-                String::from("")
-            },
-            image: if p.image.is_some() {
-                let byte_array = p.image.unwrap_or(vec![]);
-                str::from_utf8(&byte_array).unwrap_or("").to_string()
-            } else {
-                String::from("")
-            },
-        });
+        posts.push(response_post);
     }
 
     DataResponse {
         status: Status::Ok,
-        data: Json(Ok(response_posts)),
+        data: Json(Ok(BookmarksPage { posts, next_cursor })),
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct LikeInfo {
-    post_id: i32,
+pub struct VoteData {
+    option: i32,
 }
 
-#[patch(
-    "/user/like-comment",
+#[post(
+    "/user/poll-vote/<post_id>",
     format = "application/json",
-    data = "<like_info>"
+    data = "<vote>"
 )]
-pub async fn like_comment(cookies: &CookieJar<'_>, like_info: Json<LikeInfo>) -> Status {
+pub async fn vote_poll(
+    post_id: i32,
+    vote: Json<VoteData>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<ResponsePoll, &'static str>> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
     let Some(jwt) = cookies.get_private("auth_key") else {
-        return Status::BadRequest;
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
     };
     let Ok(s) = validate_jwt(jwt.value()).await else {
-        return Status::BadRequest;
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
     };
-    let like_info = like_info.into_inner();
+    let vote = vote.into_inner();
     let pool = database::connect_db().await;
 
-    let Ok(has_user_already_liked) =
-        database::comment_likes_list_contains(&pool, &like_info.post_id, &s.id).await
-    else {
-        return Status::InternalServerError;
-    };
+    match database::can_view_post(&pool, &post_id, &Some(s.id)).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return DataResponse {
+                status: Status::NotFound,
+                data: Json(Err("Poll not found")),
+            };
+        }
+        Err(..) => {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        }
+    }
 
-    if has_user_already_liked {
-        let Ok(()) = database::dislike_comment(&pool, &s.id, &like_info.post_id).await else {
-            return Status::InternalServerError;
+    let poll = match database::get_poll(&pool, &post_id, &Some(s.id)).await {
+        Ok(Some(p)) => p,
+        Ok(None) => {
+            return DataResponse {
+                status: Status::NotFound,
+                data: Json(Err("Poll not found")),
+            };
+        }
+        Err(..) => {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        }
+    };
+    if poll.closes_at <= date {
+        return DataResponse {
+            status: Status::BadRequest,
+            data: Json(Err("Poll is closed")),
         };
-    } else {
-        let Ok(()) = database::like_comment(&pool, &s.id, &like_info.post_id).await else {
-            return Status::InternalServerError;
+    }
+    if !poll.options.iter().any(|o| o.option_index == vote.option) {
+        return DataResponse {
+            status: Status::BadRequest,
+            data: Json(Err("Invalid option")),
         };
     }
 
-    Status::Ok
+    match database::vote_poll(&pool, &post_id, &s.id, &vote.option, &date).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return DataResponse {
+                status: Status::Conflict,
+                data: Json(Err("Already voted")),
+            };
+        }
+        Err(..) => {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        }
+    }
+
+    let Ok(Some(poll)) = database::get_poll(&pool, &post_id, &Some(s.id)).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(make_response_poll(poll))),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LikeState {
+    pub liked: bool,
+    #[serde(rename = "likesCount")]
+    pub likes_count: i32,
+}
+
+fn make_like_response(
+    res: Result<Option<i32>, sqlx::Error>,
+    liked: bool,
+    not_found: &'static str,
+) -> DataResponse<Result<LikeState, &'static str>> {
+    match res {
+        Ok(Some(likes_count)) => DataResponse {
+            status: Status::Ok,
+            data: Json(Ok(LikeState { liked, likes_count })),
+        },
+        Ok(None) => DataResponse {
+            status: Status::NotFound,
+            data: Json(Err(not_found)),
+        },
+        Err(..) => DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        },
+    }
 }
 
-#[patch("/user/like", format = "application/json", data = "<like_info>")]
-pub async fn like(cookies: &CookieJar<'_>, like_info: Json<LikeInfo>) -> Status {
+#[put("/user/like-comment/<comment_id>")]
+pub async fn like_comment(
+    comment_id: i32,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<LikeState, &'static str>> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
+
     let Some(jwt) = cookies.get_private("auth_key") else {
-        return Status::BadRequest;
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
     };
     let Ok(s) = validate_jwt(jwt.value()).await else {
-        return Status::BadRequest;
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
     };
-    let like_info = like_info.into_inner();
     let pool = database::connect_db().await;
 
-    let Ok(has_user_already_liked) =
-        database::likes_list_contains(&pool, &like_info.post_id, &s.id).await
-    else {
-        return Status::InternalServerError;
+    match database::can_view_comment(&pool, &comment_id, &Some(s.id)).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return DataResponse {
+                status: Status::NotFound,
+                data: Json(Err("Comment not found")),
+            };
+        }
+        Err(..) => {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        }
+    }
+
+    make_like_response(
+        database::like_comment(&pool, &s.id, &comment_id, &date).await,
+        true,
+        "Comment not found",
+    )
+}
+
+/// A like can still be taken back after a block.
+#[delete("/user/like-comment/<comment_id>")]
+pub async fn unlike_comment(
+    comment_id: i32,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<LikeState, &'static str>> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
     };
+    let pool = database::connect_db().await;
+
+    make_like_response(
+        database::dislike_comment(&pool, &s.id, &comment_id).await,
+        false,
+        "Comment not found",
+    )
+}
+
+#[put("/user/like/<post_id>")]
+pub async fn like(
+    post_id: i32,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<LikeState, &'static str>> {
+    let date = SystemTime::now();
+    let date: i64 = date
+        .duration_since(UNIX_EPOCH)
+        .expect("We're in 1969??")
+        .as_millis() as i64;
 
-    if has_user_already_liked {
-        let Ok(()) = database::dislike(&pool, &s.id, &like_info.post_id).await else {
-            return Status::InternalServerError;
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
         };
-    } else {
-        let Ok(()) = database::like(&pool, &s.id, &like_info.post_id).await else {
-            return Status::InternalServerError;
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
         };
+    };
+    let pool = database::connect_db().await;
+
+    match database::can_view_post(&pool, &post_id, &Some(s.id)).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return DataResponse {
+                status: Status::NotFound,
+                data: Json(Err("Post not found")),
+            };
+        }
+        Err(..) => {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        }
     }
 
-    Status::Ok
+    make_like_response(
+        database::like(&pool, &s.id, &post_id, &date).await,
+        true,
+        "Post not found",
+    )
+}
+
+/// A like can still be taken back after a block.
+#[delete("/user/like/<post_id>")]
+pub async fn unlike(
+    post_id: i32,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<LikeState, &'static str>> {
+    let Some(jwt) = cookies.get_private("auth_key") else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let Ok(s) = validate_jwt(jwt.value()).await else {
+        return DataResponse {
+            status: Status::Forbidden,
+            data: Json(Err("Forbidden")),
+        };
+    };
+    let pool = database::connect_db().await;
+
+    make_like_response(
+        database::dislike(&pool, &s.id, &post_id).await,
+        false,
+        "Post not found",
+    )
 }
 
 #[patch(
@@ -625,14 +1332,13 @@ pub async fn comment(
         .expect("We've just stepped on the moon! :D")
         .as_millis() as i64;
 
-    let data = post_data.into_inner();
-    const POST_MAX_CHAR_LENGTH: usize = 200;
+    let mut data = post_data.into_inner();
     let cookie = cookies.get_private("auth_key");
-    if data.text.is_none() && data.image.is_none() {
-        return Custom(Status::BadRequest, "Bad request, post was empty");
+    if let Err(e) = validate_comment_data(&data) {
+        return e;
     }
-    if data.text.is_some() && data.text.as_ref().unwrap().len() > POST_MAX_CHAR_LENGTH {
-        return Custom(Status::BadRequest, "Text too long");
+    if data.publish_at.is_some() {
+        return Custom(Status::BadRequest, "Comments can't be scheduled");
     }
     if cookie.is_none() {
         return Custom(Status::Forbidden, "Forbidden");
@@ -643,11 +1349,27 @@ pub async fn comment(
     };
 
     let pool = database::connect_db().await;
+    match database::can_view_post(&pool, &owner_post_id, &Some(s.id)).await {
+        Ok(true) => {}
+        Ok(false) => return Custom(Status::NotFound, "Post not found"),
+        Err(..) => return Custom(Status::InternalServerError, "InternalServerError"),
+    }
+    if let Err(e) = resolve_post_media(&mut data, &pool).await {
+        return e;
+    }
 
     let text = data.text.unwrap_or(String::from(""));
-    if database::comment(&s.id, &text, &data.image, &date, &pool, &owner_post_id)
-        .await
-        .is_err()
+    if database::comment(
+        &s.id,
+        &text,
+        &data.image,
+        &data.attachments,
+        &date,
+        &pool,
+        &owner_post_id,
+    )
+    .await
+    .is_err()
     {
         return Custom(Status::InternalServerError, "InternalServerError");
     };
@@ -1,20 +1,31 @@
 use core::str;
+use std::sync::LazyLock;
 
 use crate::auth::validate_jwt;
 use crate::database::{
     get_client_data, get_email_from_user_at, get_followers_list, get_following_list,
-    get_id_from_email, user_exists,
+    get_id_from_email, user_exists, FollowListEntry, Liker, PostSearch,
 };
+use crate::media::media_url;
 use crate::routes::types::ProfileData;
 use crate::validate_user_at;
+use regex::Regex;
 use rocket::{
     http::{CookieJar, Status},
     serde::json::{self, Json},
 };
 use serde::{Deserialize, Serialize};
 
-use super::types::{DataResponse, UpdatedClientUser, UpdatedFollowData};
-use super::user::ResponseComment;
+use super::mutes::{contains_muted_word, get_viewer_muted_words};
+use super::types::{
+    Cursor, DataResponse, FollowListPage, FollowListUser, UpdatedClientUser, UpdatedFollowData,
+};
+use super::user::{
+    get_viewer_id, make_response_attachments, make_response_post, ResponseAttachment,
+    ResponseComment, ResponsePost, SensitiveMedia,
+};
+
+const FOLLOWED_BY_PREVIEW_LIMIT: i64 = 3;
 
 #[get("/user/profile/<user_at>", format = "application/json")]
 pub async fn get_profile_data(
@@ -24,6 +35,12 @@ pub async fn get_profile_data(
     let jwt = cookies.get_private("auth_key");
     let mut is_following = false;
     let mut is_himself = false;
+    let mut follows_you = false;
+    let mut followed_by_count = 0;
+    let mut followed_by = vec![];
+    let mut is_blocked = false;
+    let mut is_muted = false;
+    let mut follow_requested = false;
 
     if !(validate_user_at(user_at).await).valid {
         return DataResponse {
@@ -48,7 +65,17 @@ pub async fn get_profile_data(
         };
     };
 
+    let mut pinned_post_id = None;
+
     if let Ok(id) = get_id_from_email(&email, &pool).await {
+        let Ok(p) = crate::database::get_pinned_post_id(&id, &pool).await else {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        };
+        pinned_post_id = p;
+
         if let Some(c) = jwt {
             let Ok(s) = validate_jwt(c.value()).await else {
                 return DataResponse {
@@ -68,9 +95,63 @@ pub async fn get_profile_data(
                 };
             }
 
+            // the blocker's profile doesn't exist for the blocked user
+            match crate::database::has_blocked(&id, &s.id, &pool).await {
+                Ok(false) => {}
+                Ok(true) => {
+                    return DataResponse {
+                        status: Status::NotFound,
+                        data: Json(Err("Not found")),
+                    };
+                }
+                Err(..) => {
+                    return DataResponse {
+                        status: Status::InternalServerError,
+                        data: Json(Err("InternalServerError")),
+                    };
+                }
+            }
+
             if let Ok(b) = crate::database::is_following(&id, &s.id, &pool).await {
                 is_following = b;
             }
+            if let Ok(b) = crate::database::is_following(&s.id, &id, &pool).await {
+                follows_you = b;
+            }
+            if !is_himself {
+                let Ok(preview) = crate::database::get_followed_by_preview(
+                    &s.id,
+                    &id,
+                    &FOLLOWED_BY_PREVIEW_LIMIT,
+                    &pool,
+                )
+                .await
+                else {
+                    return DataResponse {
+                        status: Status::InternalServerError,
+                        data: Json(Err("InternalServerError")),
+                    };
+                };
+                followed_by_count = preview.count;
+                followed_by = preview
+                    .users
+                    .into_iter()
+                    .map(|u| UpdatedFollowData {
+                        user_at: u.userat,
+                        username: u.username,
+                        icon: media_url(u.icon_id),
+                    })
+                    .collect();
+            }
+            if let Ok(b) = crate::database::has_blocked(&s.id, &id, &pool).await {
+                is_blocked = b;
+            }
+            if let Ok(b) = crate::database::has_muted(&s.id, &id, &pool).await {
+                is_muted = b;
+            }
+            if let Ok(b) = crate::database::has_requested_follow(&id, &s.id, &pool).await {
+                follow_requested = b;
+            }
         }
     }
 
@@ -90,16 +171,16 @@ pub async fn get_profile_data(
             following_count: data.followingcount,
             followers_count: data.followerscount,
             is_himself,
-            icon: if data.icon.is_none() {
-                String::new()
-            } else {
-                str::from_utf8(&data.icon.unwrap()).unwrap().to_owned()
-            },
-            bio: if data.bio.is_none() {
-                String::new()
-            } else {
-                data.bio.unwrap()
-            },
+            follows_you,
+            followed_by_count,
+            followed_by,
+            is_blocked,
+            is_muted,
+            is_private: data.private,
+            follow_requested,
+            icon: media_url(data.icon_id),
+            bio: data.bio.unwrap_or_default(),
+            pinned_post_id,
         })),
     }
 }
@@ -124,11 +205,8 @@ pub async fn get_data(cookies: &CookieJar<'_>) -> DataResponse<String> {
                 bio: c.bio,
                 followingcount: c.followingcount,
                 followerscount: c.followerscount,
-                icon: if c.icon.is_none() {
-                    String::new()
-                } else {
-                    str::from_utf8(&c.icon.unwrap()).unwrap().to_owned()
-                },
+                icon: media_url(c.icon_id),
+                private: c.private,
             };
             let json_string = json::to_string(&updated);
             match json_string {
@@ -155,11 +233,44 @@ pub async fn get_data(cookies: &CookieJar<'_>) -> DataResponse<String> {
     }
 }
 
-#[get("/user/following/<user_at>")]
+const FOLLOW_LIST_DEFAULT_LIMIT: i64 = 20;
+const FOLLOW_LIST_MAX_LIMIT: i64 = 100;
+
+fn make_follow_list_page(entries: Vec<FollowListEntry>, limit: i64) -> FollowListPage {
+    let next_cursor = if entries.len() as i64 == limit {
+        entries.last().map(|f| Cursor {
+            time: f.followed_at,
+            id: f.user_id,
+        })
+    } else {
+        None
+    };
+    FollowListPage {
+        users: entries
+            .into_iter()
+            .map(|f| FollowListUser {
+                user_at: f.userat,
+                username: f.username,
+                icon: media_url(f.icon_id),
+                is_following: f.is_following,
+            })
+            .collect(),
+        next_cursor,
+    }
+}
+
+#[get("/user/following/<user_at>?<limit>&<cursor>")]
 pub async fn get_following(
     user_at: &str,
-) -> DataResponse<Result<Vec<UpdatedFollowData>, &'static str>> {
+    limit: Option<i64>,
+    cursor: Option<Cursor>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<FollowListPage, &'static str>> {
+    let limit = limit
+        .unwrap_or(FOLLOW_LIST_DEFAULT_LIMIT)
+        .clamp(1, FOLLOW_LIST_MAX_LIMIT);
     let pool = crate::database::connect_db().await;
+    let viewer_id = get_viewer_id(cookies).await;
     let Ok(email) = get_email_from_user_at(user_at, &pool).await else {
         return DataResponse {
             status: Status::InternalServerError,
@@ -167,38 +278,39 @@ pub async fn get_following(
         };
     };
 
-    let Ok(v) = get_following_list(&email, &pool).await else {
+    let Ok(v) = get_following_list(
+        &email,
+        &viewer_id,
+        &limit,
+        &cursor.map(|c| (c.time, c.id)),
+        &pool,
+    )
+    .await
+    else {
         return DataResponse {
             status: Status::InternalServerError,
             data: Json(Err("InternalServerError")),
         };
     };
 
-    let mut updated: Vec<UpdatedFollowData> = vec![];
-    for f in v {
-        let u = UpdatedFollowData {
-            user_at: f.userat,
-            username: f.username,
-            icon: if f.icon.is_none() {
-                String::new()
-            } else {
-                str::from_utf8(&f.icon.unwrap()).unwrap().to_owned()
-            },
-        };
-        updated.push(u);
-    }
-
     DataResponse {
         status: Status::Ok,
-        data: Json(Ok(updated)),
+        data: Json(Ok(make_follow_list_page(v, limit))),
     }
 }
 
-#[get("/user/followers/<user_at>")]
+#[get("/user/followers/<user_at>?<limit>&<cursor>")]
 pub async fn get_followers(
     user_at: &str,
-) -> DataResponse<Result<Vec<UpdatedFollowData>, &'static str>> {
+    limit: Option<i64>,
+    cursor: Option<Cursor>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<FollowListPage, &'static str>> {
+    let limit = limit
+        .unwrap_or(FOLLOW_LIST_DEFAULT_LIMIT)
+        .clamp(1, FOLLOW_LIST_MAX_LIMIT);
     let pool = crate::database::connect_db().await;
+    let viewer_id = get_viewer_id(cookies).await;
     let Ok(email) = get_email_from_user_at(user_at, &pool).await else {
         return DataResponse {
             status: Status::InternalServerError,
@@ -206,30 +318,24 @@ pub async fn get_followers(
         };
     };
 
-    let Ok(v) = get_followers_list(&email, &pool).await else {
+    let Ok(v) = get_followers_list(
+        &email,
+        &viewer_id,
+        &limit,
+        &cursor.map(|c| (c.time, c.id)),
+        &pool,
+    )
+    .await
+    else {
         return DataResponse {
             status: Status::InternalServerError,
             data: Json(Err("InternalServerError")),
         };
     };
 
-    let mut updated: Vec<UpdatedFollowData> = vec![];
-    for f in v {
-        let u = UpdatedFollowData {
-            user_at: f.userat,
-            username: f.username,
-            icon: if f.icon.is_none() {
-                String::new()
-            } else {
-                str::from_utf8(&f.icon.unwrap()).unwrap().to_owned()
-            },
-        };
-        updated.push(u);
-    }
-
     DataResponse {
         status: Status::Ok,
-        data: Json(Ok(updated)),
+        data: Json(Ok(make_follow_list_page(v, limit))),
     }
 }
 
@@ -244,7 +350,10 @@ pub struct UserWithIcon {
 }
 
 #[get("/user/query/<query>", format = "application/json")]
-pub async fn query(query: &str) -> DataResponse<Result<Vec<UserWithIcon>, &'static str>> {
+pub async fn query(
+    query: &str,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<UserWithIcon>, &'static str>> {
     let mut query_result: Vec<UserWithIcon> = vec![];
 
     if query.trim().is_empty() {
@@ -255,7 +364,8 @@ pub async fn query(query: &str) -> DataResponse<Result<Vec<UserWithIcon>, &'stat
     }
 
     let pool = crate::database::connect_db().await;
-    let query = crate::database::query_like(query, &pool).await;
+    let viewer_id = get_viewer_id(cookies).await;
+    let query = crate::database::query_like(query, &viewer_id, &pool).await;
     let Ok(q) = query else {
         return DataResponse {
             status: Status::InternalServerError,
@@ -267,12 +377,7 @@ pub async fn query(query: &str) -> DataResponse<Result<Vec<UserWithIcon>, &'stat
         query_result.push(UserWithIcon {
             username: i.username,
             user_at: i.userat,
-            icon: if i.icon.is_some() {
-                let byte_array = i.icon.unwrap_or(vec![]);
-                str::from_utf8(&byte_array).unwrap_or("").to_string()
-            } else {
-                String::from("")
-            },
+            icon: media_url(i.icon_id),
         });
     }
 
@@ -282,6 +387,246 @@ pub async fn query(query: &str) -> DataResponse<Result<Vec<UserWithIcon>, &'stat
     }
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LikingUser {
+    #[serde(rename = "userName")]
+    pub username: String,
+    #[serde(rename = "userAt")]
+    pub user_at: String,
+    pub icon: String,
+    #[serde(rename = "isFollowing")]
+    pub is_following: bool,
+}
+
+const LIKES_DEFAULT_LIMIT: i64 = 20;
+const LIKES_MAX_LIMIT: i64 = 50;
+
+fn make_liking_users(likers: Vec<Liker>) -> Vec<LikingUser> {
+    likers
+        .into_iter()
+        .map(|l| LikingUser {
+            username: l.username,
+            user_at: l.userat,
+            icon: media_url(l.icon_id),
+            is_following: l.is_following,
+        })
+        .collect()
+}
+
+#[get("/post/<post_id>/likes?<limit>&<offset>", format = "application/json")]
+pub async fn fetch_post_likes(
+    post_id: i32,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<LikingUser>, &'static str>> {
+    let limit = limit
+        .unwrap_or(LIKES_DEFAULT_LIMIT)
+        .clamp(1, LIKES_MAX_LIMIT);
+    let offset = offset.unwrap_or(0).max(0);
+
+    let pool = crate::database::connect_db().await;
+    let viewer_id = get_viewer_id(cookies).await;
+
+    match crate::database::can_view_post(&pool, &post_id, &viewer_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return DataResponse {
+                status: Status::NotFound,
+                data: Json(Err("Not found")),
+            };
+        }
+        Err(..) => {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        }
+    }
+
+    let Ok(likers) =
+        crate::database::get_post_likers(&pool, &post_id, &viewer_id, &limit, &offset).await
+    else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(make_liking_users(likers))),
+    }
+}
+
+#[get(
+    "/comment/<comment_id>/likes?<limit>&<offset>",
+    format = "application/json"
+)]
+pub async fn fetch_comment_likes(
+    comment_id: i32,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<LikingUser>, &'static str>> {
+    let limit = limit
+        .unwrap_or(LIKES_DEFAULT_LIMIT)
+        .clamp(1, LIKES_MAX_LIMIT);
+    let offset = offset.unwrap_or(0).max(0);
+
+    let pool = crate::database::connect_db().await;
+    let viewer_id = get_viewer_id(cookies).await;
+
+    match crate::database::can_view_comment(&pool, &comment_id, &viewer_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return DataResponse {
+                status: Status::NotFound,
+                data: Json(Err("Not found")),
+            };
+        }
+        Err(..) => {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        }
+    }
+
+    let Ok(likers) =
+        crate::database::get_comment_likers(&pool, &comment_id, &viewer_id, &limit, &offset).await
+    else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(make_liking_users(likers))),
+    }
+}
+
+/// Turns the user's query into a `to_tsquery` expression. Quoted text becomes a
+/// phrase, a trailing `*` makes a prefix match and `from:user_at` filters by
+/// author, everything else has to match.
+pub fn parse_post_search(query: &str) -> PostSearch {
+    // underscores split words in to_tsquery, a lone one would leave no lexeme
+    static LEXEME_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[^\W_]+").unwrap());
+    let mut terms: Vec<String> = vec![];
+    let mut from: Option<String> = None;
+
+    for (i, part) in query.split('"').enumerate() {
+        // odd parts were inside quotes
+        if i % 2 == 1 {
+            let words: Vec<String> = LEXEME_REGEX
+                .find_iter(part)
+                .map(|m| m.as_str().to_lowercase())
+                .collect();
+            if !words.is_empty() {
+                terms.push(format!("({})", words.join(" <-> ")));
+            }
+            continue;
+        }
+        for word in part.split_whitespace() {
+            if let Some(user_at) = word.strip_prefix("from:") {
+                let user_at = user_at.trim_start_matches('@');
+                if !user_at.is_empty() {
+                    from = Some(user_at.to_lowercase());
+                }
+                continue;
+            }
+            let prefix = word.ends_with('*');
+            let words: Vec<String> = LEXEME_REGEX
+                .find_iter(word)
+                .map(|m| m.as_str().to_lowercase())
+                .collect();
+            let Some((last, rest)) = words.split_last() else {
+                continue;
+            };
+            for w in rest {
+                terms.push(w.to_owned());
+            }
+            if prefix {
+                terms.push(format!("{last}:*"));
+            } else {
+                terms.push(last.to_owned());
+            }
+        }
+    }
+
+    PostSearch {
+        tsquery: if terms.is_empty() {
+            None
+        } else {
+            Some(terms.join(" & "))
+        },
+        from,
+        since: None,
+        until: None,
+    }
+}
+
+const SEARCH_DEFAULT_LIMIT: i64 = 20;
+const SEARCH_MAX_LIMIT: i64 = 50;
+
+#[get(
+    "/user/search-posts?<q>&<since>&<until>&<limit>&<offset>",
+    format = "application/json"
+)]
+pub async fn search_posts(
+    q: &str,
+    since: Option<i64>,
+    until: Option<i64>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<ResponsePost>, &'static str>> {
+    let mut search = parse_post_search(q);
+    if search.tsquery.is_none() && search.from.is_none() {
+        return DataResponse {
+            status: Status::BadRequest,
+            data: Json(Err("Empty query")),
+        };
+    }
+    search.since = since;
+    search.until = until;
+
+    let limit = limit
+        .unwrap_or(SEARCH_DEFAULT_LIMIT)
+        .clamp(1, SEARCH_MAX_LIMIT);
+    let offset = offset.unwrap_or(0).max(0);
+
+    let pool = crate::database::connect_db().await;
+    let viewer_id = get_viewer_id(cookies).await;
+
+    let Ok(posts) =
+        crate::database::search_posts(&pool, &search, &viewer_id, &limit, &offset).await
+    else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    let mut response_posts: Vec<ResponsePost> = vec![];
+    for p in posts {
+        let Ok(response_post) = make_response_post(p, viewer_id, &pool).await else {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        };
+        response_posts.push(response_post);
+    }
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(response_posts)),
+    }
+}
+
 #[get("/user/fetch-post-comments/<post_id>", format = "application/json")]
 pub async fn fetch_comments(
     cookies: &CookieJar<'_>,
@@ -289,21 +634,42 @@ pub async fn fetch_comments(
 ) -> DataResponse<Result<Vec<ResponseComment>, &'static str>> {
     let pool = crate::database::connect_db().await;
 
-    let Ok(posts) = crate::database::get_comments_from_post(&pool, &post_id).await else {
+    let viewer_id = get_viewer_id(cookies).await;
+
+    match crate::database::can_view_post(&pool, &post_id, &viewer_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return DataResponse {
+                status: Status::NotFound,
+                data: Json(Err("Not found")),
+            };
+        }
+        Err(..) => {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        }
+    }
+
+    let Ok(posts) = crate::database::get_comments_from_post(&pool, &post_id, &viewer_id).await
+    else {
         return DataResponse {
             status: Status::InternalServerError,
             data: Json(Err("InternalServerError")),
         };
     };
-    let mut response_posts: Vec<ResponseComment> = vec![];
-
-    let mut owner_id: Option<i32> = None;
-
-    if let Some(jwt) = cookies.get_private("auth_key") {
-        if let Ok(s) = validate_jwt(jwt.value()).await {
-            owner_id = Some(s.id);
+    let Ok(muted_words) = get_viewer_muted_words(&viewer_id, &pool).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
         };
     };
+    let posts = posts.into_iter().filter(|c| {
+        Some(c.owner_id) == viewer_id
+            || !contains_muted_word(c.text.as_deref().unwrap_or_default(), &muted_words)
+    });
+    let mut response_posts: Vec<ResponseComment> = vec![];
 
     for p in posts {
         let Ok(email) = crate::database::get_email_from_id(&p.owner_id, &pool).await else {
@@ -319,22 +685,28 @@ pub async fn fetch_comments(
             };
         };
 
-        let has_this_user_liked = if owner_id.is_none() {
-            false
-        } else {
-            let Ok(c) =
-                crate::database::comment_likes_list_contains(&pool, &p.post_id, &owner_id.unwrap())
-                    .await
-            else {
-                return DataResponse {
-                    status: Status::InternalServerError,
-                    data: Json(Err("InternalServerError")),
+        let has_this_user_liked = match viewer_id {
+            Some(id) => {
+                let Ok(c) = crate::database::has_liked_comment(&pool, &p.post_id, &id).await else {
+                    return DataResponse {
+                        status: Status::InternalServerError,
+                        data: Json(Err("InternalServerError")),
+                    };
                 };
+                c
+            }
+            None => false,
+        };
+        let Ok(attachments) = crate::database::get_comment_attachments(&pool, &p.post_id).await
+        else {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
             };
-            c
         };
         response_posts.push(ResponseComment {
             has_this_user_liked,
+            attachments: make_response_attachments(attachments),
             owner_id: p.owner_id,
             post_id: p.post_id,
             unix_time: p.unix_time.to_string(),
@@ -342,23 +714,9 @@ pub async fn fetch_comments(
             username: owner_data.username,
             likes_count: p.likescount,
             comments_count: p.commentscount,
-            icon: if owner_data.icon.is_some() {
-                let byte_array = owner_data.icon.unwrap_or(vec![]);
-                str::from_utf8(&byte_array).unwrap_or("").to_string()
-            } else {
-                String::from("")
-            },
-            text: if p.text.is_some() {
-                p.text.unwrap()
-            } else {
-                String::from("")
-            },
-            image: if p.image.is_some() {
-                let byte_array = p.image.unwrap_or(vec![]);
-                str::from_utf8(&byte_array).unwrap_or("").to_string()
-            } else {
-                String::from("")
-            },
+            icon: media_url(owner_data.icon_id),
+            text: p.text.unwrap_or_default(),
+            image: media_url(p.image_id),
         });
     }
 
@@ -367,3 +725,183 @@ pub async fn fetch_comments(
         data: Json(Ok(response_posts)),
     }
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResponsePostRevision {
+    pub text: String,
+    pub image: String,
+    #[serde(rename = "contentWarning")]
+    pub content_warning: Option<String>,
+    pub sensitive: bool,
+    pub attachments: Vec<ResponseAttachment>,
+    #[serde(rename = "unixTime")]
+    pub unix_time: String,
+    #[serde(rename = "isCurrent")]
+    pub is_current: bool,
+}
+
+#[get("/user/post-history/<post_id>", format = "application/json")]
+pub async fn fetch_post_history(
+    post_id: i32,
+    cookies: &CookieJar<'_>,
+) -> DataResponse<Result<Vec<ResponsePostRevision>, &'static str>> {
+    let pool = crate::database::connect_db().await;
+    let viewer_id = get_viewer_id(cookies).await;
+
+    match crate::database::can_view_post(&pool, &post_id, &viewer_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return DataResponse {
+                status: Status::NotFound,
+                data: Json(Err("Not found")),
+            };
+        }
+        Err(..) => {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        }
+    }
+
+    let Ok(post) = crate::database::get_post_by_id(&pool, &post_id).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+    let Ok(revisions) = crate::database::get_post_revisions(&pool, &post_id).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    let Ok(attachments) = crate::database::get_post_attachments(&pool, &post_id).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+    let Ok(sensitive_media) = crate::database::get_sensitive_media(&pool, &viewer_id).await else {
+        return DataResponse {
+            status: Status::InternalServerError,
+            data: Json(Err("InternalServerError")),
+        };
+    };
+
+    let mut history: Vec<ResponsePostRevision> = vec![];
+    for r in revisions {
+        let Ok(attachments) =
+            crate::database::get_post_revision_attachments(&pool, &r.revision_id).await
+        else {
+            return DataResponse {
+                status: Status::InternalServerError,
+                data: Json(Err("InternalServerError")),
+            };
+        };
+        history.push(ResponsePostRevision {
+            text: r.text.unwrap_or_default(),
+            image: media_url(r.image_id),
+            content_warning: r.content_warning,
+            sensitive: r.sensitive,
+            attachments: make_response_attachments(attachments),
+            unix_time: r.unix_time.to_string(),
+            is_current: false,
+        });
+    }
+    history.push(ResponsePostRevision {
+        text: post.text.unwrap_or_default(),
+        image: media_url(post.image_id),
+        content_warning: post.content_warning,
+        sensitive: post.sensitive,
+        attachments: make_response_attachments(attachments),
+        unix_time: post.edited_at.unwrap_or(post.unix_time).to_string(),
+        is_current: true,
+    });
+    // like in posts, sensitive media is left out for viewers who hide it
+    if sensitive_media == SensitiveMedia::Hide {
+        for r in history.iter_mut().filter(|r| r.sensitive) {
+            r.image = String::new();
+            r.attachments.clear();
+        }
+    }
+
+    DataResponse {
+        status: Status::Ok,
+        data: Json(Ok(history)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tsquery(query: &str) -> Option<String> {
+        parse_post_search(query).tsquery
+    }
+
+    #[test]
+    fn parses_words() {
+        assert_eq!(tsquery("Rust  async"), Some("rust & async".to_string()));
+        // punctuation splits words instead of reaching to_tsquery
+        assert_eq!(
+            tsquery("don't (panic) & | !"),
+            Some("don & t & panic".to_string())
+        );
+        assert_eq!(tsquery("snake_case"), Some("snake & case".to_string()));
+    }
+
+    #[test]
+    fn parses_phrases() {
+        assert_eq!(
+            tsquery(r#"say "Hello,  World" now"#),
+            Some("say & (hello <-> world) & now".to_string())
+        );
+        assert_eq!(tsquery(r#""""#), None);
+        assert_eq!(tsquery(r#""!?" x"#), Some("x".to_string()));
+    }
+
+    #[test]
+    fn parses_prefixes() {
+        assert_eq!(tsquery("prog*"), Some("prog:*".to_string()));
+        assert_eq!(tsquery("foo-ba* baz"), Some("foo & ba:* & baz".to_string()));
+        assert_eq!(tsquery("*"), None);
+        assert_eq!(tsquery(r#""prog*""#), Some("(prog)".to_string()));
+    }
+
+    #[test]
+    fn parses_from() {
+        let search = parse_post_search("from:@Alice cats");
+        assert_eq!(search.from.as_deref(), Some("alice"));
+        assert_eq!(search.tsquery, Some("cats".to_string()));
+
+        let search = parse_post_search("from:bob");
+        assert_eq!(search.from.as_deref(), Some("bob"));
+        assert_eq!(search.tsquery, None);
+
+        let search = parse_post_search("from: cats");
+        assert_eq!(search.from, None);
+        assert_eq!(search.tsquery, Some("cats".to_string()));
+    }
+
+    #[test]
+    fn parses_unbalanced_quotes() {
+        assert_eq!(
+            tsquery(r#"one "two three"#),
+            Some("one & (two <-> three)".to_string())
+        );
+        assert_eq!(
+            tsquery(r#"a "b" c "d"#),
+            Some("a & (b) & c & (d)".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_empty_queries() {
+        for query in ["", "   ", "\"", "!!!", "* & |", "_", "\"_\" __*", "from:"] {
+            let search = parse_post_search(query);
+            assert_eq!(search.tsquery, None, "{query:?}");
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Orbit, Rocket,
+};
+
+use crate::database;
+
+const SCHEDULED_POSTS_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Spawns the background worker that publishes scheduled posts once their
+/// `publish_at` has passed.
+pub struct ScheduledPosts;
+
+#[rocket::async_trait]
+impl Fairing for ScheduledPosts {
+    fn info(&self) -> Info {
+        Info {
+            name: "Publish scheduled posts",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, _rocket: &Rocket<Orbit>) {
+        tokio::spawn(async {
+            let pool = database::connect_db().await;
+            let mut interval = tokio::time::interval(SCHEDULED_POSTS_INTERVAL);
+            loop {
+                interval.tick().await;
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("We're in 1969??")
+                    .as_millis() as i64;
+                if let Err(e) = database::publish_due_posts(&now, &pool).await {
+                    error!("unable to publish scheduled posts: {e}");
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use sqlx::{Error, Pool, Postgres};
+
+use crate::database;
+
+/// How long a user's ranking is reused before it's computed again. Follows,
+/// blocks and dismissals made in the meantime are still filtered out on every
+/// read.
+const SUGGESTIONS_TTL: Duration = Duration::from_secs(10 * 60);
+const SUGGESTIONS_ACTIVITY_WINDOW_MILLIS: i64 = 7 * 24 * 60 * 60 * 1000;
+const SUGGESTIONS_FRIENDS_WEIGHT: f64 = 3.0;
+const SUGGESTIONS_FOLLOWERS_WEIGHT: f64 = 2.0;
+const SUGGESTIONS_ACTIVITY_WEIGHT: f64 = 1.0;
+/// How many ranked accounts are kept per user, enough to refill the list as
+/// the user follows or dismisses some of them.
+pub const SUGGESTIONS_CACHED: i64 = 50;
+
+struct CachedSuggestions {
+    computed_at: Instant,
+    user_ids: Vec<i32>,
+}
+
+/// Each user's last ranked suggestions, computed on demand.
+#[derive(Default, Clone)]
+pub struct SuggestionsCache(Arc<RwLock<HashMap<i32, CachedSuggestions>>>);
+
+impl SuggestionsCache {
+    /// The ranked ids of the accounts suggested to the user, from the cache if
+    /// they're fresh enough.
+    pub async fn get(&self, user_id: &i32, pool: &Pool<Postgres>) -> Result<Vec<i32>, Error> {
+        if let Some(cached) = self.0.read().unwrap().get(user_id) {
+            if cached.computed_at.elapsed() < SUGGESTIONS_TTL {
+                return Ok(cached.user_ids.clone());
+            }
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("We're in 1969??")
+            .as_millis() as i64;
+        let user_ids = database::get_suggested_user_ids(
+            user_id,
+            &(now - SUGGESTIONS_ACTIVITY_WINDOW_MILLIS),
+            &SUGGESTIONS_FRIENDS_WEIGHT,
+            &SUGGESTIONS_FOLLOWERS_WEIGHT,
+            &SUGGESTIONS_ACTIVITY_WEIGHT,
+            &SUGGESTIONS_CACHED,
+            pool,
+        )
+        .await?;
+
+        let mut cache = self.0.write().unwrap();
+        // drop the users who haven't asked in a while so the map doesn't grow forever
+        cache.retain(|_, c| c.computed_at.elapsed() < SUGGESTIONS_TTL);
+        cache.insert(
+            *user_id,
+            CachedSuggestions {
+                computed_at: Instant::now(),
+                user_ids: user_ids.clone(),
+            },
+        );
+        Ok(user_ids)
+    }
+}
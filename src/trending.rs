@@ -0,0 +1,116 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Orbit, Rocket,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, Pool, Postgres};
+
+use crate::database;
+
+const TRENDING_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const TRENDING_WINDOW_MILLIS: i64 = 48 * 60 * 60 * 1000;
+/// Hashtags used in this window are compared against the rest of
+/// `TRENDING_WINDOW_MILLIS` to get their velocity.
+const TRENDING_TAGS_RECENT_MILLIS: i64 = 6 * 60 * 60 * 1000;
+const TRENDING_LIKE_WEIGHT: f64 = 1.0;
+const TRENDING_COMMENT_WEIGHT: f64 = 2.0;
+const TRENDING_GRAVITY: f64 = 1.5;
+const TRENDING_POSTS_LIMIT: i64 = 20;
+const TRENDING_TAGS_LIMIT: usize = 10;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TrendingTag {
+    pub tag: String,
+    #[serde(rename = "postsCount")]
+    pub posts_count: i64,
+    pub velocity: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct Trending {
+    pub post_ids: Vec<i32>,
+    pub tags: Vec<TrendingTag>,
+}
+
+/// Last computed trending results, shared between the worker and the routes.
+#[derive(Default, Clone)]
+pub struct TrendingCache(pub Arc<RwLock<Trending>>);
+
+async fn compute_trending(now: i64, pool: &Pool<Postgres>) -> Result<Trending, Error> {
+    let since = now - TRENDING_WINDOW_MILLIS;
+    let recent_since = now - TRENDING_TAGS_RECENT_MILLIS;
+
+    let post_ids = database::get_trending_post_ids(
+        pool,
+        &now,
+        &since,
+        &TRENDING_LIKE_WEIGHT,
+        &TRENDING_COMMENT_WEIGHT,
+        &TRENDING_GRAVITY,
+        &TRENDING_POSTS_LIMIT,
+    )
+    .await?;
+
+    // how many times longer the baseline is than the recent window
+    let baseline_ratio = (TRENDING_WINDOW_MILLIS - TRENDING_TAGS_RECENT_MILLIS) as f64
+        / TRENDING_TAGS_RECENT_MILLIS as f64;
+    let mut tags: Vec<TrendingTag> = database::get_hashtag_counts(pool, &since, &recent_since)
+        .await?
+        .into_iter()
+        .filter(|t| t.recent > 0)
+        .map(|t| TrendingTag {
+            velocity: t.recent as f64 / (t.previous as f64 / baseline_ratio + 1.0),
+            posts_count: t.recent + t.previous,
+            tag: t.tag,
+        })
+        .collect();
+    tags.sort_by(|a, b| {
+        b.velocity
+            .total_cmp(&a.velocity)
+            .then(b.posts_count.cmp(&a.posts_count))
+    });
+    tags.truncate(TRENDING_TAGS_LIMIT);
+
+    Ok(Trending { post_ids, tags })
+}
+
+/// Spawns the background worker that periodically recomputes the trending
+/// posts and hashtags into the [`TrendingCache`].
+pub struct TrendingWorker;
+
+#[rocket::async_trait]
+impl Fairing for TrendingWorker {
+    fn info(&self) -> Info {
+        Info {
+            name: "Recompute trending posts and hashtags",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let Some(cache) = rocket.state::<TrendingCache>().cloned() else {
+            error!("TrendingCache is not managed, trending won't be computed");
+            return;
+        };
+        tokio::spawn(async move {
+            let pool = database::connect_db().await;
+            let mut interval = tokio::time::interval(TRENDING_INTERVAL);
+            loop {
+                interval.tick().await;
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("We're in 1969??")
+                    .as_millis() as i64;
+                match compute_trending(now, &pool).await {
+                    Ok(t) => *cache.0.write().unwrap() = t,
+                    Err(e) => error!("unable to compute trending: {e}"),
+                }
+            }
+        });
+    }
+}